@@ -22,6 +22,10 @@ struct Cli {
 
     #[arg(short, long)]
     address: Url,
+
+    /// Maximum bytes of stdout and stderr that are sent each. The middle of longer outputs is cut out
+    #[arg(short, long, default_value_t = 512 * 1024)]
+    max_output: usize,
 }
 
 #[tokio::main]
@@ -44,6 +48,9 @@ async fn main() {
         .expect("Invalid type for url! Did not provide a correct url. https://example.com/")
         .clone();
     address.set_path("/job-update");
+    let max_output = *args
+        .get_one::<usize>("max_output")
+        .expect("Invalid type for 'max_output'");
 
     let mut request = GuardedRequest {
         password: password.clone(),
@@ -88,9 +95,16 @@ async fn main() {
     let stdout = str::from_utf8(&output.stdout).expect("Failed to get stdout of program");
     let stderr = str::from_utf8(&output.stderr).expect("Failed to get stderr of program");
 
+    let job_output = JobOutput {
+        stdout: truncate(stdout, max_output),
+        stderr: truncate(stderr, max_output),
+        exit_code: output.status.code(),
+        signal: exit_signal(&output.status),
+    };
+
     let response_update = match success {
-        true => Update::FinishedJob(String::from(stdout)),
-        false => Update::Error(String::from(stderr)),
+        true => Update::FinishedJob(job_output),
+        false => Update::Error(job_output),
     };
 
     request.data.update = response_update;
@@ -104,6 +118,7 @@ async fn main() {
         )
         .send()
         .await
+        .and_then(|v| v.error_for_status())
     {
         println!("Error sending final request: {}", e)
     }
@@ -126,6 +141,46 @@ pub struct ClientUpdate {
 #[derive(Serialize, Debug, Clone)]
 enum Update {
     StartingJob,
-    FinishedJob(String),
-    Error(String),
+    FinishedJob(JobOutput),
+    Error(JobOutput),
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct JobOutput {
+    stdout: String,
+    stderr: String,
+    exit_code: Option<i32>,
+    signal: Option<i32>,
+}
+
+/// Keeps the start and the end of the output and marks where it was cut
+fn truncate(output: &str, max: usize) -> String {
+    if output.len() <= max {
+        return String::from(output);
+    }
+    let mut head_end = max / 2;
+    while !output.is_char_boundary(head_end) {
+        head_end -= 1;
+    }
+    let mut tail_start = output.len() - max / 2;
+    while !output.is_char_boundary(tail_start) {
+        tail_start += 1;
+    }
+    format!(
+        "{}\n[... {} bytes truncated ...]\n{}",
+        &output[..head_end],
+        tail_start - head_end,
+        &output[tail_start..]
+    )
+}
+
+#[cfg(unix)]
+fn exit_signal(status: &std::process::ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &std::process::ExitStatus) -> Option<i32> {
+    None
 }
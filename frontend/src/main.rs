@@ -1,10 +1,13 @@
 use {
     leptos::*,
     reqwest::StatusCode,
-    serde::{Deserialize, Serialize},
-    std::time::SystemTime,
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
+    std::time::{Duration, SystemTime},
     url::Url,
-    web_sys::{wasm_bindgen::JsCast, HtmlInputElement},
+    web_sys::{
+        wasm_bindgen::{JsCast, JsValue},
+        HtmlInputElement,
+    },
 };
 
 fn main() {
@@ -89,6 +92,7 @@ fn App() -> impl IntoView {
                         ResponseStatus::Success(v) => {
                             view! {
                                 <h1>Status</h1>
+                                <Search/>
                                 {
                                     v.into_iter().map(|n| view! {<Job name=n></Job>}).collect_view()
                                 }
@@ -190,7 +194,7 @@ fn Job(name: String) -> impl IntoView {
 #[component]
 fn JobData(job_status: JobStatus) -> impl IntoView {
     view! {
-        <div class="jobData"><h3>{job_status.job.id}</h3>
+        <div class="jobData"><h3>{job_status.job.id.clone()}</h3>
         {
             if let Some(v) = job_status.hostname {
                 view! {
@@ -212,11 +216,60 @@ fn JobData(job_status: JobStatus) -> impl IntoView {
                 view! {}.into_view()
             }
         }
-        <Log log=job_status.log.unwrap_or_default()/>
+        {
+            if let Some(v) = job_status.failure {
+                view! {
+                    <div class="failure">{v}</div>
+                }.into_view()
+            }
+            else {
+                view! {}.into_view()
+            }
+        }
+        {
+            match (job_status.problem, job_status.job.escalation) {
+                (Some(problem), Some(_)) => view! {
+                    <Acknowledge job_id=job_status.job.id.clone() problem=problem/>
+                }.into_view(),
+                _ => view! {}.into_view(),
+            }
+        }
+        <Stats name=job_status.job.id.clone()/>
+        <Incidents name=job_status.job.id.clone()/>
+        {
+            match job_status.output {
+                Some(v) => view! {<Output output=v/>}.into_view(),
+                None => view! {<Log log=String::new()/>}.into_view(),
+            }
+        }
         </div>
     }
 }
 
+#[component]
+fn Acknowledge(job_id: String, problem: Problem) -> impl IntoView {
+    match problem.acknowledged {
+        Some(v) => {
+            let text = match v.by {
+                Some(by) => format!("Acknowledged by {} at {}", by, format_time(v.time)),
+                None => format!("Acknowledged at {}", format_time(v.time)),
+            };
+            view! {<div class="acknowledged">{text}</div>}.into_view()
+        }
+        None => view! {
+            <button class="acknowledge" on:click=move |_| {
+                let job_id = job_id.clone();
+                spawn_local(async move {
+                    // the response has no body, the dashboard is reloaded either way
+                    let _ = post::<_, ()>("/acknowledge", AcknowledgeRequest { job_id, by: None }).await;
+                    reload();
+                });
+            }>Acknowledge</button>
+        }
+        .into_view(),
+    }
+}
+
 #[component]
 fn IconAttribute(icon_path: String, text: String) -> impl IntoView {
     view! {
@@ -224,6 +277,209 @@ fn IconAttribute(icon_path: String, text: String) -> impl IntoView {
     }
 }
 
+#[component]
+fn Search() -> impl IntoView {
+    let (query, set_query) = create_signal(String::new());
+    let results = create_resource(
+        move || query.get(),
+        |query| async move {
+            if query.is_empty() {
+                return ResponseStatus::Success(Vec::new());
+            }
+            post::<_, Vec<SearchResult>>("/search-logs", SearchRequest { query }).await
+        },
+    );
+
+    view! {
+        <div class="jobData">
+        <input placeholder="Search logs" class="searchInput" on:change=move |v| set_query.set(v.target().unwrap().dyn_into::<HtmlInputElement>().unwrap().value()) />
+        {move || match results.get() {
+            Some(ResponseStatus::Success(v)) => {
+                v.into_iter().map(|v| view! {<SearchHit result=v/>}).collect_view()
+            }
+            Some(_) => "Error searching logs".into_view(),
+            None => "Searching...".into_view(),
+        }}
+        </div>
+    }
+}
+
+#[component]
+fn SearchHit(result: SearchResult) -> impl IntoView {
+    view! {
+        <div class="searchHit">
+        <b>{result.job_id}</b>
+        <a>{format!("{} ({})", format_timestamp(&result.ended), result.stream)}</a>
+        <div class="log snippet">
+            {result.snippet.before}<mark>{result.snippet.matched}</mark>{result.snippet.after}
+        </div>
+        </div>
+    }
+}
+
+#[component]
+fn Stats(name: String) -> impl IntoView {
+    let stats = create_resource(
+        move || name.clone(),
+        |name| async move { post::<_, Vec<JobStats>>("/get-job-stats", name).await },
+    );
+
+    view! {
+        <div class="stats">
+        {move || match stats.get() {
+            Some(ResponseStatus::Success(v)) => {
+                v.into_iter().map(|v| view! {<StatsWindow stats=v/>}).collect_view()
+            }
+            Some(_) => "Error loading stats".into_view(),
+            None => view! {}.into_view(),
+        }}
+        </div>
+    }
+}
+
+#[component]
+fn Incidents(name: String) -> impl IntoView {
+    let incidents = create_resource(
+        move || name.clone(),
+        |job_id| async move {
+            post::<_, IncidentPage>(
+                "/get-incidents",
+                IncidentsRequest {
+                    job_id,
+                    per_page: 5,
+                },
+            )
+            .await
+        },
+    );
+
+    view! {
+        {move || match incidents.get() {
+            Some(ResponseStatus::Success(v)) if !v.incidents.is_empty() => view! {
+                <div class="incidents">
+                <b>{format!("Incidents ({})", v.total)}</b>
+                {v.incidents.into_iter().map(|v| view! {<IncidentRow incident=v/>}).collect_view()}
+                </div>
+            }.into_view(),
+            Some(ResponseStatus::Success(_)) => view! {}.into_view(),
+            Some(_) => "Error loading incidents".into_view(),
+            None => view! {}.into_view(),
+        }}
+    }
+}
+
+#[component]
+fn IncidentRow(incident: Incident) -> impl IntoView {
+    let (open, set_open) = create_signal(false);
+    let period = match &incident.closed {
+        Some(v) => format!(
+            "{} - {}",
+            format_timestamp(&incident.opened),
+            format_timestamp(v)
+        ),
+        None => format!("{} - open", format_timestamp(&incident.opened)),
+    };
+    view! {
+        <div class="incident">
+        <a class="incidentTitle" on:click=move |_| set_open.update(|v| *v = !*v)>
+            {format!("#{} {}: {}", incident.id, incident.kind, period)}
+        </a>
+        {move || match open.get() {
+            true => view! {<Timeline id=incident.id/>}.into_view(),
+            false => view! {}.into_view(),
+        }}
+        </div>
+    }
+}
+
+#[component]
+fn Timeline(id: i64) -> impl IntoView {
+    let details = create_resource(
+        || {},
+        move |_| async move { post::<_, IncidentDetails>("/get-incident", id).await },
+    );
+
+    view! {
+        {move || match details.get() {
+            Some(ResponseStatus::Success(v)) => view! {
+                <div class="timeline">
+                {v.timeline.into_iter().map(|v| {
+                    let text = match v.author {
+                        Some(author) => format!("{} ({}): {}", v.kind, author, v.text),
+                        None => format!("{}: {}", v.kind, v.text),
+                    };
+                    view! {<div><a class="timelineTime">{format_timestamp(&v.time)}</a>" "{text}</div>}
+                }).collect_view()}
+                <input placeholder="Comment" class="commentInput" on:change=move |v| {
+                    let text = v.target().unwrap().dyn_into::<HtmlInputElement>().unwrap().value();
+                    spawn_local(async move {
+                        // the response has no body
+                        let _ = post::<_, ()>("/comment-incident", CommentRequest { incident_id: id, text }).await;
+                        details.refetch();
+                    });
+                } />
+                </div>
+            }.into_view(),
+            Some(_) => "Error loading incident".into_view(),
+            None => view! {}.into_view(),
+        }}
+    }
+}
+
+#[component]
+fn StatsWindow(stats: JobStats) -> impl IntoView {
+    let percent = |v: Option<f64>| match v {
+        Some(v) => format!("{:.1}%", v * 100.0),
+        None => "-".to_string(),
+    };
+    let seconds = |v: Option<Duration>| match v {
+        Some(v) => format!("{:.1}s", v.as_secs_f64()),
+        None => "-".to_string(),
+    };
+    view! {
+        <div class="statsWindow">
+        <b>{stats.window}</b>
+        <a>{format!("success {}", percent(stats.success_rate))}</a>
+        <a>{format!("on time {}", percent(stats.on_time_rate))}</a>
+        <a>{format!("missed {}", stats.missed)}</a>
+        <a>{format!("mean {}", seconds(stats.mean_duration))}</a>
+        <a>{format!("p95 {}", seconds(stats.p95_duration))}</a>
+        </div>
+    }
+}
+
+#[component]
+fn Output(output: JobOutput) -> impl IntoView {
+    let exit = match (output.exit_code, output.signal) {
+        (_, Some(signal)) => Some(format!("Killed by signal {}", signal)),
+        (Some(code), None) => Some(format!("Exit code {}", code)),
+        (None, None) => None,
+    };
+    view! {
+        {
+            if let Some(v) = exit {
+                view! {
+                    <IconAttribute icon_path="icons/terminal.svg".to_string() text={v}/>
+                }
+            }
+            else {
+                view! {}.into_view()
+            }
+        }
+        <Log log=output.stdout/>
+        {
+            if !output.stderr.is_empty() {
+                view! {
+                    <div class="log stderr">{output.stderr}</div>
+                }.into_view()
+            }
+            else {
+                view! {}.into_view()
+            }
+        }
+    }
+}
+
 #[component]
 fn Log(log: String) -> impl IntoView {
     view! {
@@ -258,6 +514,61 @@ impl<T> GuardedRequest<T> {
     }
 }
 
+async fn post<D: Serialize, T: DeserializeOwned>(path: &str, data: D) -> ResponseStatus<T> {
+    let request = match GuardedRequest::new(data) {
+        Some(v) => v,
+        None => return ResponseStatus::Unauthorized,
+    };
+    let client = reqwest::Client::new();
+    let res = match client
+        .post(
+            Url::parse(&leptos::window().origin())
+                .unwrap()
+                .join(path)
+                .unwrap(),
+        )
+        .body(serde_json::to_string(&request).unwrap())
+        .send()
+        .await
+    {
+        Ok(v) => {
+            if let StatusCode::UNAUTHORIZED = v.status() {
+                return ResponseStatus::Unauthorized;
+            }
+
+            match v.text().await {
+                Ok(v) => v,
+                Err(_) => return ResponseStatus::ParseError,
+            }
+        }
+        Err(_) => {
+            return ResponseStatus::RequestError;
+        }
+    };
+
+    match serde_json::from_str(&res) {
+        Ok(v) => ResponseStatus::Success(v),
+        Err(_) => ResponseStatus::ParseError,
+    }
+}
+
+fn format_time(time: SystemTime) -> String {
+    let millis = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as f64;
+    format_date(js_sys::Date::new(&millis.into()))
+}
+
+/// Formats the RFC 3339 times of the history, incidents and search results
+fn format_timestamp(time: &str) -> String {
+    format_date(js_sys::Date::new(&time.into()))
+}
+
+fn format_date(date: js_sys::Date) -> String {
+    date.to_locale_string("default", &JsValue::UNDEFINED).into()
+}
+
 fn get_password_cookie() -> Option<String> {
     let html_doc: web_sys::HtmlDocument = document().dyn_into().unwrap(); //document should always be cast-able to HtmlDocument
     let cookies = html_doc.cookie().unwrap(); //cookies are always present
@@ -286,16 +597,35 @@ pub struct Job {
     pub execution_time: String,
     #[serde(default)]
     pub id: String,
-    pub hook: Option<Url>,
+    pub escalation: Option<String>,
 }
 
 #[derive(Clone, Deserialize, Debug)]
 pub struct JobStatus {
     job: Job,
     status: Status,
-    log: Option<String>,
+    output: Option<JobOutput>,
+    failure: Option<String>,
     hostname: Option<String>,
     command: Option<String>,
+    problem: Option<Problem>,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Problem {
+    acknowledged: Option<Acknowledgement>,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Acknowledgement {
+    by: Option<String>,
+    time: SystemTime,
+}
+
+#[derive(Serialize, Clone)]
+struct AcknowledgeRequest {
+    job_id: String,
+    by: Option<String>,
 }
 
 #[derive(Clone, Deserialize, Debug)]
@@ -307,3 +637,80 @@ pub enum Status {
     WaitingForResponse(SystemTime),
     ClientError,
 }
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct JobOutput {
+    stdout: String,
+    stderr: String,
+    exit_code: Option<i32>,
+    signal: Option<i32>,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct JobStats {
+    window: String,
+    missed: usize,
+    success_rate: Option<f64>,
+    on_time_rate: Option<f64>,
+    mean_duration: Option<Duration>,
+    p95_duration: Option<Duration>,
+}
+
+#[derive(Serialize, Clone)]
+struct IncidentsRequest {
+    job_id: String,
+    per_page: usize,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct IncidentPage {
+    incidents: Vec<Incident>,
+    total: usize,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Incident {
+    id: i64,
+    kind: String,
+    opened: String,
+    closed: Option<String>,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct IncidentDetails {
+    timeline: Vec<TimelineEntry>,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct TimelineEntry {
+    time: String,
+    kind: String,
+    author: Option<String>,
+    text: String,
+}
+
+#[derive(Serialize, Clone)]
+struct CommentRequest {
+    incident_id: i64,
+    text: String,
+}
+
+#[derive(Serialize, Clone)]
+struct SearchRequest {
+    query: String,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct SearchResult {
+    job_id: String,
+    ended: String,
+    stream: String,
+    snippet: Snippet,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Snippet {
+    before: String,
+    matched: String,
+    after: String,
+}
@@ -0,0 +1,79 @@
+use std::{
+    env,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Writes the list of dashboard files the `embed-frontend` feature builds into the binary
+fn main() {
+    println!("cargo:rerun-if-env-changed=CRONTAB_STATUS_DIST");
+    if env::var_os("CARGO_FEATURE_EMBED_FRONTEND").is_none() {
+        return;
+    }
+    let dist = match env::var_os("CRONTAB_STATUS_DIST") {
+        Some(v) => PathBuf::from(v),
+        None => Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("../frontend/dist"),
+    };
+    println!("cargo:rerun-if-changed={}", dist.display());
+    let dist = dist.canonicalize().unwrap_or_else(|_| {
+        panic!(
+            "{} doesn't exist, run `trunk build --release` in the frontend directory first",
+            dist.display()
+        )
+    });
+
+    let mut files = Vec::new();
+    collect(&dist, &mut files);
+    files.sort();
+    let mut assets = String::from("pub static ASSETS: &[Asset] = &[\n");
+    for file in files {
+        let path = file
+            .strip_prefix(&dist)
+            .unwrap()
+            .components()
+            .map(|v| v.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let content = fs::read(&file).unwrap();
+        writeln!(
+            assets,
+            "    Asset {{ path: {:?}, etag: {:?}, immutable: {}, content: include_bytes!({:?}) }},",
+            path,
+            format!("\"{:016x}\"", fnv1a(&content)),
+            hashed(&path),
+            file
+        )
+        .unwrap();
+    }
+    assets.push_str("];\n");
+    fs::write(
+        Path::new(&env::var("OUT_DIR").unwrap()).join("assets.rs"),
+        assets,
+    )
+    .unwrap();
+}
+
+fn collect(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+/// Trunk puts a 16 digit hash of the content into the names of the scripts, wasm and styles
+fn hashed(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.split(['-', '_', '.'])
+        .any(|v| v.len() == 16 && v.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn fnv1a(content: &[u8]) -> u64 {
+    content.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,152 @@
+use {
+    regex::Regex,
+    serde::{Deserialize, Serialize, Serializer},
+};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct OutputAssertions {
+    #[serde(default)]
+    pub must_match: Vec<OutputPattern>,
+    #[serde(default)]
+    pub must_not_match: Vec<OutputPattern>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    #[serde(default)]
+    pub stream: OutputStream,
+}
+
+/// Which output of the job the assertions are checked against
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+    #[default]
+    Both,
+}
+
+impl OutputAssertions {
+    /// Returns the reason of the first rule the output violates
+    pub fn check(&self, stdout: &str, stderr: &str) -> Result<(), String> {
+        let output = match self.stream {
+            OutputStream::Stdout => stdout.to_string(),
+            OutputStream::Stderr => stderr.to_string(),
+            OutputStream::Both if stderr.is_empty() => stdout.to_string(),
+            OutputStream::Both => format!("{}\n{}", stdout, stderr),
+        };
+        let output = output.as_str();
+        for pattern in self.must_match.iter() {
+            if !pattern.0.is_match(output) {
+                return Err(format!("Output did not match '{}'", pattern.0));
+            }
+        }
+        for pattern in self.must_not_match.iter() {
+            if let Some(found) = pattern.0.find(output) {
+                return Err(format!(
+                    "Output matched '{}': {}",
+                    pattern.0,
+                    found.as_str()
+                ));
+            }
+        }
+        if let Some(min) = self.min_length {
+            if output.len() < min {
+                return Err(format!(
+                    "Output is {} bytes long, expected at least {}",
+                    output.len(),
+                    min
+                ));
+            }
+        }
+        if let Some(max) = self.max_length {
+            if output.len() > max {
+                return Err(format!(
+                    "Output is {} bytes long, expected at most {}",
+                    output.len(),
+                    max
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutputPattern(pub Regex);
+
+impl Serialize for OutputPattern {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assertions(v: &str) -> OutputAssertions {
+        toml::from_str(v).unwrap()
+    }
+
+    #[test]
+    fn must_match() {
+        let assertions = assertions(r#"must_match = ["(?i)backup", "(?m)^done$"]"#);
+        assert_eq!(assertions.check("Backup\ndone", ""), Ok(()));
+        assert_eq!(
+            assertions.check("backup", ""),
+            Err(String::from("Output did not match '(?m)^done$'"))
+        );
+        assert_eq!(
+            assertions.check("done", ""),
+            Err(String::from("Output did not match '(?i)backup'"))
+        );
+    }
+
+    #[test]
+    fn must_not_match() {
+        let assertions = assertions(r#"must_not_match = ["ERROR: \\w+"]"#);
+        assert_eq!(assertions.check("all good", "warning"), Ok(()));
+        assert_eq!(
+            assertions.check("ok", "ERROR: disk full"),
+            Err(String::from("Output matched 'ERROR: \\w+': ERROR: disk"))
+        );
+    }
+
+    #[test]
+    fn checks_the_chosen_stream() {
+        let both = assertions(r#"must_not_match = ["ERROR"]"#);
+        let stdout = assertions("must_not_match = [\"ERROR\"]\nstream = \"stdout\"");
+        let stderr = assertions("must_not_match = [\"ERROR\"]\nstream = \"stderr\"");
+        assert!(both.check("", "ERROR").is_err());
+        assert!(both.check("ERROR", "").is_err());
+        assert_eq!(stdout.check("", "ERROR"), Ok(()));
+        assert!(stdout.check("ERROR", "").is_err());
+        assert_eq!(stderr.check("ERROR", ""), Ok(()));
+        assert!(stderr.check("", "ERROR").is_err());
+    }
+
+    #[test]
+    fn length() {
+        let assertions = assertions("min_length = 3\nmax_length = 5");
+        assert_eq!(
+            assertions.check("ab", ""),
+            Err(String::from("Output is 2 bytes long, expected at least 3"))
+        );
+        assert_eq!(assertions.check("abc", ""), Ok(()));
+        assert_eq!(assertions.check("abcde", ""), Ok(()));
+        assert_eq!(
+            assertions.check("abcdef", ""),
+            Err(String::from("Output is 6 bytes long, expected at most 5"))
+        );
+        // both streams are joined by a newline
+        assert_eq!(assertions.check("ab", "c"), Ok(()));
+    }
+
+    #[test]
+    fn no_rules() {
+        assert_eq!(OutputAssertions::default().check("", "ERROR"), Ok(()));
+    }
+}
@@ -1,23 +1,60 @@
 use {
     crate::{
-        cron::{CronExecutionTime, Job, TimeValue},
-        error::ConfigResult,
+        assertion::OutputPattern,
+        cron::{CronExecutionTime, Job, JobManager, Reconciled, TimeValue},
+        digest::DigestConfig,
+        error::{ConfigError, ConfigResult},
+        history::HistoryRetention,
+        logs::LogLimits,
+        notifier::{Hook, HookOptions, SmtpConfig, Target},
+        route::{Escalation, Route},
+        template::Template,
     },
     serde::{
-        de::{self, Visitor},
+        de::{self, value::MapAccessDeserializer, MapAccess, Visitor},
         Deserialize,
     },
-    std::collections::HashMap,
-    tokio::{fs::File, io::AsyncReadExt},
+    std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+        sync::{Arc, RwLock},
+        time::{Duration, SystemTime},
+    },
+    tokio::{fs::File, io::AsyncReadExt, sync::Mutex, time::sleep},
 };
 
+/// How often the config file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
     pub password: String,
     pub port: u16,
+    #[serde(default = "default_database")]
+    pub database: PathBuf,
+    #[serde(default)]
+    pub history: HistoryRetention,
+    #[serde(default)]
+    pub logs: LogLimits,
+    /// Mail server of the `email` hooks
+    pub smtp: Option<SmtpConfig>,
+    /// Hooks the `routes` refer to by name
+    #[serde(default)]
+    pub notifiers: HashMap<String, Hook>,
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    /// Referred to by the `escalation` of jobs
+    #[serde(default)]
+    pub escalations: HashMap<String, Escalation>,
+    /// Summary of every job, sent on a schedule
+    pub digest: Option<DigestConfig>,
     pub jobs: HashMap<String, Job>,
 }
 
+fn default_database() -> PathBuf {
+    PathBuf::from("crontab_status.db")
+}
+
 impl<'de> Deserialize<'de> for CronExecutionTime {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -153,20 +190,311 @@ impl<'de> Visitor<'de> for CronExecutionTimeVisitor {
     }
 }
 
+impl<'de> Deserialize<'de> for OutputPattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_str(OutputPatternVisitor)
+    }
+}
+
+struct OutputPatternVisitor;
+impl<'de> Visitor<'de> for OutputPatternVisitor {
+    type Value = OutputPattern;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a valid regular expression")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        match regex::Regex::new(v) {
+            Ok(v) => Ok(OutputPattern(v)),
+            Err(e) => Err(E::custom(format!("invalid regex: {}", e))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Hook {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(HookVisitor)
+    }
+}
+
+struct HookVisitor;
+impl<'de> Visitor<'de> for HookVisitor {
+    type Value = Hook;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a webhook url or a table with the `kind` and target of the hook")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        match url::Url::parse(v) {
+            Ok(url) => Ok(Hook::new(Target::Json { url })),
+            Err(e) => Err(E::custom(format!("invalid url: {}", e))),
+        }
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut table = toml::Table::deserialize(MapAccessDeserializer::new(map))?;
+        if !table.contains_key("kind") {
+            table.insert(String::from("kind"), toml::Value::from("json"));
+        }
+        let table = toml::Value::Table(table);
+        let options = HookOptions::deserialize(table.clone()).map_err(de::Error::custom)?;
+        let target = Target::deserialize(table).map_err(de::Error::custom)?;
+        for (name, template) in options.headers.iter() {
+            if let Err(e) = reqwest::header::HeaderName::from_bytes(name.as_bytes()) {
+                return Err(de::Error::custom(format!("invalid header {}: {}", name, e)));
+            }
+            if let Some(placeholder) = template.multiline_placeholder() {
+                return Err(de::Error::custom(format!(
+                    "header {} uses {{{{{}}}}}, which can span lines, use {{{{{} | json}}}}",
+                    name, placeholder, placeholder
+                )));
+            }
+        }
+        if let Target::Matrix { homeserver, .. } = &target {
+            if homeserver.cannot_be_a_base() {
+                return Err(de::Error::custom("the homeserver has to be an http(s) url"));
+            }
+        }
+        if let Target::Command { command } = &target {
+            if command.is_empty() {
+                return Err(de::Error::custom("the command needs at least a program"));
+            }
+        }
+        Ok(Hook {
+            name: None,
+            target,
+            options,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Template {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_str(TemplateVisitor)
+    }
+}
+
+struct TemplateVisitor;
+impl<'de> Visitor<'de> for TemplateVisitor {
+    type Value = Template;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a template with {{placeholders}}")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Template::parse(v).map_err(|e| E::custom(format!("invalid template: {}", e)))
+    }
+}
+
 impl Config {
-    pub async fn load() -> ConfigResult<Self> {
+    pub async fn load(path: &Path) -> ConfigResult<Self> {
         let mut config = String::new();
-        File::open("config.toml")
-            .await?
-            .read_to_string(&mut config)
-            .await?;
+        File::open(path).await?.read_to_string(&mut config).await?;
         let mut parsed = toml::from_str::<Config>(&config)?;
         let string_default = String::default();
         for (name, job) in parsed.jobs.iter_mut() {
             if job.id == string_default {
                 job.id = name.clone();
             }
+            if job.history.is_none() {
+                job.history = Some(parsed.history.clone());
+            }
+            if let Some(hook) = job.hook.take() {
+                job.hooks.insert(0, hook);
+            }
+            if parsed.smtp.is_none() && job.hooks.iter().any(|v| v.is_email()) {
+                return Err(ConfigError::InvalidConfig(format!(
+                    "job {} has an email hook, but there is no [smtp] section",
+                    name
+                )));
+            }
+        }
+        for (name, notifier) in parsed.notifiers.iter_mut() {
+            notifier.name = Some(name.clone());
+            if parsed.smtp.is_none() && notifier.is_email() {
+                return Err(ConfigError::InvalidConfig(format!(
+                    "notifier {} sends emails, but there is no [smtp] section",
+                    name
+                )));
+            }
+        }
+        for name in parsed.routes.iter().flat_map(|v| v.notifiers.iter()) {
+            if !parsed.notifiers.contains_key(name) {
+                return Err(ConfigError::InvalidConfig(format!(
+                    "a route uses the notifier {}, which doesn't exist",
+                    name
+                )));
+            }
+        }
+        for (name, escalation) in parsed.escalations.iter() {
+            if escalation.steps.is_empty() {
+                return Err(ConfigError::InvalidConfig(format!(
+                    "escalation {} has no steps",
+                    name
+                )));
+            }
+            if let Some(notifier) = escalation
+                .notifiers(escalation.steps.len())
+                .find(|v| !parsed.notifiers.contains_key(*v))
+            {
+                return Err(ConfigError::InvalidConfig(format!(
+                    "escalation {} uses the notifier {}, which doesn't exist",
+                    name, notifier
+                )));
+            }
+        }
+        for (name, job) in parsed.jobs.iter() {
+            if let Some(escalation) = &job.escalation {
+                if !parsed.escalations.contains_key(escalation) {
+                    return Err(ConfigError::InvalidConfig(format!(
+                        "job {} uses the escalation {}, which doesn't exist",
+                        name, escalation
+                    )));
+                }
+            }
+        }
+        if let Some(digest) = &parsed.digest {
+            if let CronExecutionTime::Reboot = digest.schedule {
+                return Err(ConfigError::InvalidConfig(
+                    "the digest needs a schedule with times, not @reboot".to_string(),
+                ));
+            }
+            if let Some(name) = digest
+                .notifiers
+                .iter()
+                .find(|v| !parsed.notifiers.contains_key(*v))
+            {
+                return Err(ConfigError::InvalidConfig(format!(
+                    "the digest uses the notifier {}, which doesn't exist",
+                    name
+                )));
+            }
         }
         Ok(parsed)
     }
 }
+
+/// The config in effect, replaced when the file is reloaded
+#[derive(Clone)]
+pub struct SharedConfig {
+    path: PathBuf,
+    current: Arc<RwLock<Arc<Config>>>,
+    /// Keeps reloads from overlapping, so the jobs and the config stay in step
+    reloading: Arc<Mutex<()>>,
+}
+
+impl SharedConfig {
+    pub fn new(config: Config, path: PathBuf) -> Self {
+        Self {
+            path,
+            current: Arc::new(RwLock::new(Arc::new(config))),
+            reloading: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn get(&self) -> Arc<Config> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Loads the config file again and hands it to `manager`. If the file is invalid, the current
+    /// config stays in effect
+    pub async fn reload(&self, manager: &JobManager) -> ConfigResult<Reconciled> {
+        let _reloading = self.reloading.lock().await;
+        let config = Config::load(&self.path).await?;
+        let reconciled = manager.reload(&config).await?;
+        let current = self.get();
+        for (setting, changed) in [
+            ("port", config.port != current.port),
+            ("database", config.database != current.database),
+            (
+                "logs.max_body_size",
+                config.logs.max_body_size != current.logs.max_body_size,
+            ),
+        ] {
+            if changed {
+                println!("The new {} is used once the server restarts", setting)
+            }
+        }
+        *self.current.write().unwrap() = Arc::new(config);
+        Ok(reconciled)
+    }
+
+    /// Reloads the config whenever the file changes or the server gets SIGHUP
+    pub fn watch(&self, manager: JobManager) {
+        let config = self.clone();
+        let watch_manager = manager.clone();
+        tokio::spawn(async move {
+            let mut seen = modified(&config.path).await;
+            loop {
+                sleep(WATCH_INTERVAL).await;
+                let now = modified(&config.path).await;
+                if now != seen {
+                    seen = now;
+                    config.log_reload(&watch_manager).await;
+                }
+            }
+        });
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let config = self.clone();
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(v) => v,
+                Err(e) => {
+                    println!("Unable to listen for SIGHUP: {}", e);
+                    return;
+                }
+            };
+            tokio::spawn(async move {
+                while hangup.recv().await.is_some() {
+                    config.log_reload(&manager).await;
+                }
+            });
+        }
+    }
+
+    async fn log_reload(&self, manager: &JobManager) {
+        match self.reload(manager).await {
+            Ok(v) => println!(
+                "Reloaded {}: added [{}], removed [{}], changed [{}]",
+                self.path.display(),
+                v.added.join(", "),
+                v.removed.join(", "),
+                v.changed.join(", ")
+            ),
+            Err(e) => println!(
+                "Unable to reload {}, keeping the current config: {}",
+                self.path.display(),
+                e
+            ),
+        }
+    }
+}
+
+async fn modified(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.ok()?.modified().ok()
+}
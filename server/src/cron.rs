@@ -1,5 +1,18 @@
 use {
-    crate::error::{ConfigError, ConfigResult},
+    crate::{
+        assertion::OutputAssertions,
+        config::Config,
+        digest::{Digest, DigestConfig},
+        error::{ConfigError, ConfigResult, StoreResult},
+        history::{HistoryPage, HistoryRetention, Run, RunOutcome},
+        incident::{self, IncidentDetails, IncidentPage, TimelineEntry, TimelineKind},
+        logs::{self, LogLimits},
+        metrics::JobMetrics,
+        notifier::{Event, EventKind, Hook, Notifier, DELIVERY_RETENTION, EXCERPT},
+        route::{Escalation, Severity},
+        stats::{JobStats, OUTCOME_RETENTION, WINDOWS},
+        store::Store,
+    },
     chrono::{DateTime, Datelike, Timelike, Utc},
     serde::{Deserialize, Serialize, Serializer},
     std::{
@@ -8,7 +21,6 @@ use {
         time::{Duration, SystemTime},
     },
     tokio::{sync::RwLock, time::sleep},
-    url::Url,
 };
 
 #[derive(Deserialize, Debug, Clone, Serialize)]
@@ -16,7 +28,30 @@ pub struct Job {
     pub execution_time: CronExecutionTime,
     #[serde(default)]
     pub id: String,
-    pub hook: Option<Url>,
+    /// Shorthand for a single entry in `hooks`
+    #[serde(skip_serializing)]
+    pub hook: Option<Hook>,
+    #[serde(default)]
+    pub hooks: Vec<Hook>,
+    /// Used by `routes` to pick the notifiers of the job
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub severity: Severity,
+    #[serde(default)]
+    pub assertions: OutputAssertions,
+    pub history: Option<HistoryRetention>,
+    /// Seconds a run may start after its schedule and still count as on time
+    #[serde(default = "default_grace")]
+    pub grace: u64,
+    /// Seconds a run may take before the hooks are told it timed out
+    pub max_runtime: Option<u64>,
+    /// Name of the escalation its problems go through until they are acknowledged
+    pub escalation: Option<String>,
+}
+
+fn default_grace() -> u64 {
+    60
 }
 
 #[derive(Debug, Clone)]
@@ -30,13 +65,18 @@ impl Serialize for CronExecutionTime {
     where
         S: Serializer,
     {
-        let res = match self {
-            CronExecutionTime::Reboot => String::from("@reboot"),
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl std::fmt::Display for CronExecutionTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CronExecutionTime::Reboot => write!(f, "@reboot"),
             CronExecutionTime::Timing(v1, v2, v3, v4, v5) => {
-                format!("{} {} {} {} {}", v1, v2, v3, v4, v5)
+                write!(f, "{} {} {} {} {}", v1, v2, v3, v4, v5)
             }
-        };
-        serializer.serialize_str(&res)
+        }
     }
 }
 
@@ -44,7 +84,7 @@ impl CronExecutionTime {
     pub fn matches(&self, time: DateTime<Utc>) -> bool {
         let timing = match self {
             CronExecutionTime::Reboot => return false,
-            CronExecutionTime::Timing(t1, t2, t3, t4, t5) => (t1, t2, t3, t4, t5),
+            CronExecutionTime::Timing(t1, t2, _, _, _) => (t1, t2),
         };
         let fits = (
             match timing.0 {
@@ -55,15 +95,27 @@ impl CronExecutionTime {
                 TimeValue::Every => true,
                 TimeValue::Explicit(t) => &(time.hour() as u8) == t,
             },
-            match timing.2 {
+        );
+
+        fits.0 && fits.1 && self.matches_date(time)
+    }
+
+    /// Only checks day, month and weekday
+    fn matches_date(&self, time: DateTime<Utc>) -> bool {
+        let timing = match self {
+            CronExecutionTime::Reboot => return false,
+            CronExecutionTime::Timing(_, _, t3, t4, t5) => (t3, t4, t5),
+        };
+        let fits = (
+            match timing.0 {
                 TimeValue::Every => true,
                 TimeValue::Explicit(t) => &(time.day() as u8) == t,
             },
-            match timing.3 {
+            match timing.1 {
                 TimeValue::Every => true,
                 TimeValue::Explicit(t) => &(time.month() as u8) == t,
             },
-            match timing.4 {
+            match timing.2 {
                 TimeValue::Every => true,
                 TimeValue::Explicit(t) => {
                     let weekday = &((time.weekday() as u8) + 1);
@@ -72,12 +124,75 @@ impl CronExecutionTime {
             },
         );
 
-        fits.0 && fits.1 && fits.3 && (fits.2 || fits.4)
+        // like cron, a day and a weekday match either one, otherwise only the restricted one
+        // counts
+        fits.1
+            && match (timing.0, timing.2) {
+                (TimeValue::Every, _) => fits.2,
+                (_, TimeValue::Every) => fits.0,
+                _ => fits.0 || fits.2,
+            }
     }
 
     pub fn now(&self) -> bool {
         self.matches(Utc::now())
     }
+
+    /// Every minute in `(from, to]` the job was scheduled for
+    pub fn scheduled_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        let mut scheduled = Vec::new();
+        let mut time = match from.with_second(0).and_then(|v| v.with_nanosecond(0)) {
+            Some(v) => v + chrono::Duration::minutes(1),
+            None => return scheduled,
+        };
+        while time <= to {
+            if self.matches(time) {
+                scheduled.push(time);
+            }
+            time += chrono::Duration::minutes(1);
+        }
+        scheduled
+    }
+
+    /// Whether the job was scheduled at most `grace` before `time`
+    pub fn scheduled_within(&self, time: DateTime<Utc>, grace: Duration) -> bool {
+        let grace = chrono::Duration::from_std(grace).unwrap_or_default();
+        self.matches(time) || !self.scheduled_between(time - grace, time).is_empty()
+    }
+
+    /// The last minute before `to` the job was scheduled for, looking at most a year back
+    pub fn previous_before(&self, to: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = to.with_second(0)?.with_nanosecond(0)? - chrono::Duration::minutes(1);
+        let end = to - chrono::Duration::days(366);
+        while time >= end {
+            if self.matches(time) {
+                return Some(time);
+            }
+            time -= chrono::Duration::minutes(1);
+        }
+        None
+    }
+
+    /// The first minute after `from` the job is scheduled for, looking at most 5 years ahead
+    pub fn next_after(&self, from: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = from.with_second(0)?.with_nanosecond(0)? + chrono::Duration::minutes(1);
+        let end = from + chrono::Duration::days(5 * 366);
+        while time <= end {
+            if !self.matches_date(time) {
+                time = time
+                    .date_naive()
+                    .succ_opt()?
+                    .and_hms_opt(0, 0, 0)?
+                    .and_utc();
+                continue;
+            }
+            if self.matches(time) {
+                return Some(time);
+            }
+            time += chrono::Duration::minutes(1);
+        }
+        None
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -95,138 +210,920 @@ impl std::fmt::Display for TimeValue {
     }
 }
 
-#[derive(Clone, Serialize, Debug, PartialEq)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
 pub enum Status {
     Running(SystemTime),
     Finished(SystemTime),
+    #[default]
     Unknown,
     ExpectingResponse,
     WaitingForResponse(SystemTime),
     ClientError,
 }
 
+impl Status {
+    pub const NAMES: [&'static str; 6] = [
+        "running",
+        "finished",
+        "unknown",
+        "expecting_response",
+        "waiting_for_response",
+        "client_error",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Running(_) => Status::NAMES[0],
+            Status::Finished(_) => Status::NAMES[1],
+            Status::Unknown => Status::NAMES[2],
+            Status::ExpectingResponse => Status::NAMES[3],
+            Status::WaitingForResponse(_) => Status::NAMES[4],
+            Status::ClientError => Status::NAMES[5],
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Debug)]
 pub struct JobStatus {
     job: Job,
+    #[serde(flatten)]
+    state: JobState,
+}
+
+/// The part of a [JobStatus] that is persisted across server restarts
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct JobState {
     status: Status,
-    log: Option<String>,
+    output: Option<JobOutput>,
+    failure: Option<String>,
     hostname: Option<String>,
     command: Option<String>,
+    #[serde(default)]
+    runs: u64,
+    #[serde(default)]
+    failures: u64,
+    #[serde(default)]
+    missed: u64,
+    last_success: Option<SystemTime>,
+    last_duration: Option<Duration>,
+    /// Cleared once the job succeeds again
+    problem: Option<Problem>,
+    /// Whether the current run already timed out
+    #[serde(default)]
+    timed_out: bool,
 }
 
-impl JobStatus {
-    pub fn new(job: Job) -> Self {
-        Self {
-            job,
-            status: Status::Unknown,
-            log: None,
-            hostname: None,
-            command: None,
+/// A failed, missed or timed out run that wasn't followed by a successful one yet
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Problem {
+    /// The event that opened the problem
+    kind: EventKind,
+    since: SystemTime,
+    /// When each hook was last notified about the problem, by the key of the hook
+    #[serde(default)]
+    notified: HashMap<String, SystemTime>,
+    /// Steps of the escalation that were sent
+    #[serde(default)]
+    escalated: usize,
+    /// Stops the escalation
+    acknowledged: Option<Acknowledgement>,
+    /// Id of the stored incident
+    incident: Option<i64>,
+}
+
+impl Problem {
+    /// Records that the hook with `key` is told about the problem at `time`, returns `false`
+    /// instead if it was told before and only wants to hear about it once
+    fn alarm(&mut self, key: String, dedup: bool, time: SystemTime) -> bool {
+        if dedup && self.notified.contains_key(&key) {
+            return false;
         }
+        self.notified.insert(key, time);
+        true
     }
 
-    pub fn update(&mut self) {
-        match self.status {
-            Status::Unknown => {
-                if self.job.execution_time.now() {
-                    self.status = Status::ExpectingResponse
-                }
+    /// Records a reminder to the hook with `key` if it didn't hear about the problem for
+    /// `interval`, returns whether it is due
+    fn remind(&mut self, key: String, interval: Duration, time: SystemTime) -> bool {
+        let last = self.notified.get(&key).copied().unwrap_or(self.since);
+        if time.duration_since(last).unwrap_or_default() < interval {
+            return false;
+        }
+        self.notified.insert(key, time);
+        true
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Acknowledgement {
+    pub by: Option<String>,
+    pub time: SystemTime,
+}
+
+impl JobState {
+    fn open_problem(&mut self, kind: EventKind) {
+        self.problem.get_or_insert_with(|| Problem {
+            kind,
+            since: SystemTime::now(),
+            notified: HashMap::new(),
+            escalated: 0,
+            acknowledged: None,
+            incident: None,
+        });
+    }
+
+    fn count(&mut self, run: &Run) {
+        match run.outcome {
+            RunOutcome::Succeeded => {
+                self.runs += 1;
+                self.last_success = Some(run.ended);
             }
-            Status::Finished(time) => {
-                if self.job.execution_time.now() {
-                    let now: DateTime<Utc> = chrono::DateTime::from(SystemTime::now());
-                    let happened: DateTime<Utc> = chrono::DateTime::from(time);
-                    if now.minute() != happened.minute()
-                        || now.hour() != happened.hour()
-                        || now.day() != happened.day()
-                        || now.month() != happened.month()
-                    {
-                        self.status = Status::ExpectingResponse
-                    }
+            RunOutcome::Failed => {
+                self.runs += 1;
+                self.failures += 1;
+            }
+            RunOutcome::Missed => self.missed += 1,
+        }
+        if run.outcome != RunOutcome::Missed {
+            self.last_duration = run.duration;
+        }
+    }
+}
+
+/// The run a job update produced and the events it triggered
+#[derive(Default)]
+pub struct Transition {
+    run: Option<Run>,
+    events: Vec<EventKind>,
+    /// The problem the run recovered from
+    closed: Option<Problem>,
+}
+
+impl JobStatus {
+    pub fn new(job: Job, state: JobState) -> Self {
+        Self { job, state }
+    }
+
+    /// Flags missed and timed out runs
+    pub fn update(&mut self) -> Transition {
+        match self.state.status {
+            Status::Unknown if self.job.execution_time.now() => {
+                self.state.status = Status::ExpectingResponse;
+                Transition::default()
+            }
+            Status::Finished(time) if self.job.execution_time.now() => {
+                let now: DateTime<Utc> = chrono::DateTime::from(SystemTime::now());
+                let happened: DateTime<Utc> = chrono::DateTime::from(time);
+                if now.minute() != happened.minute()
+                    || now.hour() != happened.hour()
+                    || now.day() != happened.day()
+                    || now.month() != happened.month()
+                {
+                    self.state.status = Status::ExpectingResponse
                 }
+                Transition::default()
             }
             Status::ExpectingResponse => {
-                if let Some(ref url) = self.job.hook {
-                    let url = url.clone();
-                    call_hook(url)
+                let now = SystemTime::now();
+                self.state.status = Status::WaitingForResponse(now);
+                let run = Run::new(&self.job.id, RunOutcome::Missed, now);
+                self.state.count(&run);
+                self.state.open_problem(EventKind::Missed);
+                Transition {
+                    run: Some(run),
+                    events: vec![EventKind::Missed],
+                    closed: None,
+                }
+            }
+            Status::Running(since) if !self.state.timed_out => {
+                let limit = match self.job.max_runtime {
+                    Some(v) => Duration::from_secs(v),
+                    None => return Transition::default(),
+                };
+                if since.elapsed().unwrap_or_default() < limit {
+                    return Transition::default();
+                }
+                self.state.timed_out = true;
+                self.state.open_problem(EventKind::TimedOut);
+                Transition {
+                    run: None,
+                    events: vec![EventKind::TimedOut],
+                    closed: None,
                 }
-                self.status = Status::WaitingForResponse(SystemTime::now())
             }
-            _ => {}
+            _ => Transition::default(),
         }
     }
 
-    pub fn client_update(&mut self, update: ClientUpdate) {
-        self.hostname = Some(update.hostname);
-        self.command = Some(update.command);
-        match update.update {
+    /// Records the finished run once the client reported its result
+    pub fn client_update(&mut self, update: ClientUpdate) -> Transition {
+        self.state.hostname = Some(update.hostname);
+        self.state.command = Some(update.command);
+        let started = match self.state.status {
+            Status::Running(v) => Some(v),
+            _ => None,
+        };
+        let outcome = match update.update {
             Update::StartingJob => {
-                self.log = None;
-                self.status = Status::Running(SystemTime::now());
+                self.state.output = None;
+                self.state.failure = None;
+                self.state.timed_out = false;
+                self.state.status = Status::Running(SystemTime::now());
+                return Transition {
+                    run: None,
+                    events: vec![EventKind::Started],
+                    closed: None,
+                };
             }
-            Update::FinishedJob(log) => {
-                self.log = Some(log);
-                self.status = Status::Finished(SystemTime::now());
+            Update::FinishedJob(output) => {
+                let checked = self.job.assertions.check(&output.stdout, &output.stderr);
+                self.state.output = Some(output);
+                match checked {
+                    Ok(()) => {
+                        self.state.failure = None;
+                        self.state.status = Status::Finished(SystemTime::now());
+                        RunOutcome::Succeeded
+                    }
+                    Err(reason) => {
+                        self.state.failure = Some(reason);
+                        self.state.status = Status::ClientError;
+                        RunOutcome::Failed
+                    }
+                }
             }
-            Update::Error(err) => {
-                self.log = Some(err);
-                if let Some(v) = &self.job.hook
-                {
-                    call_hook(v.clone());
+            Update::Error(output) => {
+                self.state.failure = match (output.exit_code, output.signal) {
+                    (_, Some(signal)) => Some(format!("Killed by signal {}", signal)),
+                    (Some(code), None) => Some(format!("Exited with code {}", code)),
+                    (None, None) => None,
+                };
+                self.state.output = Some(output);
+                self.state.status = Status::ClientError;
+                RunOutcome::Failed
+            }
+        };
+
+        let mut run = Run::new(&self.job.id, outcome, SystemTime::now());
+        run.started = started;
+        run.duration = started.and_then(|v| run.ended.duration_since(v).ok());
+        run.hostname = self.state.hostname.clone();
+        run.command = self.state.command.clone();
+        if let Some(output) = &self.state.output {
+            run.exit_code = output.exit_code;
+            run.signal = output.signal;
+            run.stdout = Some(output.stdout.clone());
+            run.stderr = Some(output.stderr.clone());
+        }
+        run.failure = self.state.failure.clone();
+        self.state.count(&run);
+        if run.outcome == RunOutcome::Failed {
+            self.state.open_problem(EventKind::Failed);
+            return Transition {
+                run: Some(run),
+                events: vec![EventKind::Failed],
+                closed: None,
+            };
+        }
+        let closed = self.state.problem.take();
+        Transition {
+            run: Some(run),
+            events: std::iter::once(EventKind::Succeeded)
+                .chain(closed.as_ref().map(|_| EventKind::Recovered))
+                .collect(),
+            closed,
+        }
+    }
+
+    /// Sends the events of `transition` to the hooks of the job
+    fn notify(&mut self, notifier: &Notifier, transition: &Transition) {
+        let closed = transition.closed.as_ref().and_then(|v| v.incident);
+        for kind in transition.events.iter() {
+            let mut event = self.event(*kind, transition.run.as_ref());
+            event.incident_id = event.incident_id.or(closed);
+            let targets = notifier.targets(&self.job.hooks, &event);
+            // the notifiers the problem was escalated to hear about the recovery too
+            if let (EventKind::Recovered, Some(closed), Some(escalation)) =
+                (kind, &transition.closed, self.escalation(notifier))
+            {
+                let escalated = escalation.notifiers(closed.escalated).filter(|v| {
+                    !targets
+                        .iter()
+                        .any(|(_, hook)| hook.name.as_ref() == Some(v))
+                });
+                notifier.send_named(escalated, &event);
+            }
+            for (key, hook) in targets {
+                if let (true, Some(problem)) = (kind.is_alarm(), &mut self.state.problem) {
+                    if !problem.alarm(key, hook.options.dedup, event.time) {
+                        continue;
+                    }
+                }
+                notifier.send(hook, &event);
+            }
+        }
+    }
+
+    fn escalation<'a>(&self, notifier: &'a Notifier) -> Option<&'a Escalation> {
+        notifier.escalation(self.job.escalation.as_ref()?)
+    }
+
+    /// Sends the open problem to the steps of the escalation that are due unless it was
+    /// acknowledged, returns whether it did
+    fn escalate(&mut self, notifier: &Notifier) -> bool {
+        let Some(escalation) = self.escalation(notifier) else {
+            return false;
+        };
+        let Some(kind) = self.state.problem.as_ref().map(|v| v.kind) else {
+            return false;
+        };
+        let mut event = self.event(kind, None);
+        event.failure = self.state.failure.clone();
+        let Some(problem) = &mut self.state.problem else {
+            return false;
+        };
+        let mut escalated = false;
+        while problem.acknowledged.is_none() {
+            let Some(due) = escalation.due(problem.escalated, problem.since) else {
+                break;
+            };
+            if event.time < due {
+                break;
+            }
+            event.escalation_step = Some(problem.escalated + 1);
+            let step = &escalation.steps[problem.escalated];
+            notifier.send_named(&step.notifiers, &event);
+            if let Some(incident_id) = problem.incident {
+                let text = format!(
+                    "Escalated to step {}: {}",
+                    problem.escalated + 1,
+                    step.notifiers.join(", ")
+                );
+                incident::record(
+                    notifier.store(),
+                    &TimelineEntry::new(incident_id, TimelineKind::Escalated, text),
+                );
+            }
+            problem.escalated += 1;
+            escalated = true;
+        }
+        escalated
+    }
+
+    /// Stops the escalation of the open problem, returns `false` if there is none
+    pub fn acknowledge(&mut self, by: Option<String>) -> bool {
+        let Some(problem) = &mut self.state.problem else {
+            return false;
+        };
+        problem.acknowledged.get_or_insert(Acknowledgement {
+            by,
+            time: SystemTime::now(),
+        });
+        true
+    }
+
+    /// Reminds the hooks with a `reminder_interval` of the open problem, returns whether it did
+    fn remind(&mut self, notifier: &Notifier) -> bool {
+        let Some(kind) = self.state.problem.as_ref().map(|v| v.kind) else {
+            return false;
+        };
+        let mut event = self.event(kind, None);
+        event.reminder = true;
+        event.failure = self.state.failure.clone();
+        let mut reminded = false;
+        for (key, hook) in notifier.targets(&self.job.hooks, &event) {
+            let (Some(interval), Some(problem)) =
+                (hook.options.reminder_interval, &mut self.state.problem)
+            else {
+                continue;
+            };
+            if !problem.remind(key, Duration::from_secs(interval), event.time) {
+                continue;
+            }
+            notifier.send(hook, &event);
+            reminded = true;
+        }
+        reminded
+    }
+
+    fn event(&self, kind: EventKind, run: Option<&Run>) -> Event {
+        let excerpt =
+            |log: &Option<String>| log.as_deref().map(|v| logs::tail(v, EXCERPT).to_string());
+        Event {
+            event: kind,
+            reminder: false,
+            job_id: self.job.id.clone(),
+            tags: self.job.tags.clone(),
+            severity: self.job.severity,
+            schedule: self.job.execution_time.to_string(),
+            hostname: self.state.hostname.clone(),
+            command: self.state.command.clone(),
+            time: SystemTime::now(),
+            started: match (run, &self.state.status) {
+                (Some(run), _) => run.started,
+                (None, Status::Running(v)) => Some(*v),
+                (None, _) => None,
+            },
+            ended: run.map(|v| v.ended),
+            duration: run.and_then(|v| v.duration),
+            exit_code: run.and_then(|v| v.exit_code),
+            signal: run.and_then(|v| v.signal),
+            failure: run.and_then(|v| v.failure.clone()),
+            escalation_step: None,
+            incident_id: self.state.problem.as_ref().and_then(|v| v.incident),
+            stdout: run.and_then(|v| excerpt(&v.stdout)),
+            stderr: run.and_then(|v| excerpt(&v.stderr)),
+        }
+    }
+
+    /// Opens an incident for a new problem, adds the run of `transition` to the timeline and
+    /// closes the incident the job recovered from. Has to happen before the events are sent, so
+    /// they carry the id of the incident
+    fn track_incident(&mut self, store: &Store, transition: &Transition, run_id: Option<i64>) {
+        if let Some(problem) = &mut self.state.problem {
+            if problem.incident.is_none() {
+                match store.open_incident(&self.job.id, problem.kind, problem.since) {
+                    Ok(id) => {
+                        problem.incident = Some(id);
+                        let text = format!("Job {} {}", self.job.id, problem.kind.describe());
+                        incident::record(
+                            store,
+                            &TimelineEntry::new(id, TimelineKind::Opened, text),
+                        );
+                    }
+                    Err(e) => println!("Unable to open incident of job {}: {}", self.job.id, e),
                 }
-                self.status = Status::ClientError;
             }
         }
+        let closed = transition.closed.as_ref().and_then(|v| v.incident);
+        let Some(incident_id) = self
+            .state
+            .problem
+            .as_ref()
+            .and_then(|v| v.incident)
+            .or(closed)
+        else {
+            return;
+        };
+        if let (Some(run), Some(run_id)) = (&transition.run, run_id) {
+            let text = match &run.failure {
+                Some(failure) => format!("{}: {}", run.outcome.as_str(), failure),
+                None => run.outcome.as_str().to_string(),
+            };
+            let mut entry = TimelineEntry::new(incident_id, TimelineKind::Run, text);
+            entry.run_id = Some(run_id);
+            incident::record(store, &entry);
+        }
+        if let Some(closed) = closed {
+            if let Err(e) = store.close_incident(closed, SystemTime::now()) {
+                println!("Unable to close incident {}: {}", closed, e)
+            }
+            let text = format!("Job {} recovered", self.job.id);
+            incident::record(
+                store,
+                &TimelineEntry::new(closed, TimelineKind::Closed, text),
+            );
+        }
+    }
+
+    /// Flags the job if it was scheduled while the server was down and returns the missed runs
+    /// with the [EventKind::Missed] transition of the last one. The heartbeat is only written once
+    /// a minute, so schedules before the last stored run (`last_ended`) or the last finished one
+    /// were handled before the server went down
+    fn restore(
+        &mut self,
+        down_since: SystemTime,
+        last_ended: Option<SystemTime>,
+    ) -> (Vec<Run>, Transition) {
+        let finished = match self.state.status {
+            Status::Running(_) => return (Vec::new(), Transition::default()),
+            Status::Finished(v) => Some(v),
+            _ => None,
+        };
+        let since = [Some(down_since), last_ended, finished]
+            .into_iter()
+            .flatten()
+            .max()
+            .unwrap_or(down_since);
+        let missed = self
+            .job
+            .execution_time
+            .scheduled_between(since.into(), Utc::now());
+        if missed.is_empty() {
+            return (Vec::new(), Transition::default());
+        }
+        println!(
+            "Job {} missed {} run(s) while the server was down",
+            self.job.id,
+            missed.len()
+        );
+        let runs: Vec<Run> = missed
+            .into_iter()
+            .map(|v| Run::new(&self.job.id, RunOutcome::Missed, v.into()))
+            .collect();
+        for run in runs.iter() {
+            self.state.count(run);
+        }
+        // the runs are recorded already, going through ExpectingResponse would add another one
+        self.state.status = Status::WaitingForResponse(SystemTime::now());
+        self.state.open_problem(EventKind::Missed);
+        let transition = Transition {
+            run: runs.last().cloned(),
+            events: vec![EventKind::Missed],
+            closed: None,
+        };
+        (runs, transition)
+    }
+
+    pub fn metrics(&self) -> JobMetrics {
+        JobMetrics {
+            job_id: self.job.id.clone(),
+            status: self.state.status.name(),
+            last_success: self.state.last_success,
+            last_duration: self.state.last_duration,
+            next_run: self
+                .job
+                .execution_time
+                .next_after(Utc::now())
+                .map(SystemTime::from),
+            runs: self.state.runs,
+            failures: self.state.failures,
+            missed: self.state.missed,
+        }
+    }
+
+    fn retention(&self) -> HistoryRetention {
+        self.job.history.clone().unwrap_or_default()
     }
 }
 
+/// Keeps the state of every job. Which jobs there are and how they are reported changes when the
+/// config is reloaded
+#[derive(Clone)]
 pub struct JobManager {
-    jobs: Arc<HashMap<String, RwLock<JobStatus>>>,
+    current: Arc<RwLock<Jobs>>,
+    store: Arc<Store>,
+}
+
+/// The part of the [JobManager] that comes from the config
+#[derive(Clone)]
+struct Jobs {
+    jobs: HashMap<String, Arc<RwLock<JobStatus>>>,
+    log_limits: LogLimits,
+    notifier: Notifier,
+    digest: Option<DigestConfig>,
+}
+
+/// What a reload of the config did to the jobs
+#[derive(Serialize, Debug, Default)]
+pub struct Reconciled {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Jobs with a new schedule, hooks or other settings. They keep their state
+    pub changed: Vec<String>,
 }
 
 impl JobManager {
-    pub fn new(config_jobs: HashMap<String, Job>) -> Self {
+    pub fn new(
+        config_jobs: HashMap<String, Job>,
+        log_limits: LogLimits,
+        store: Arc<Store>,
+        notifier: Notifier,
+        digest: Option<DigestConfig>,
+    ) -> Self {
+        let down_since = store.last_heartbeat().unwrap_or_else(|e| {
+            println!("Unable to read last heartbeat: {}", e);
+            None
+        });
         let mut jobs = HashMap::new();
         for (key, job) in config_jobs {
-            jobs.insert(key.clone(), RwLock::new(JobStatus::new(job)));
+            let mut status = load_status(&store, &key, job);
+            if let Some(down_since) = down_since {
+                let last_ended = match store.runs(&key, 0, 1) {
+                    Ok(v) => v.first().map(|v| v.ended),
+                    Err(e) => {
+                        println!("Unable to read last run of job {}: {}", key, e);
+                        None
+                    }
+                };
+                let (runs, transition) = status.restore(down_since, last_ended);
+                if !runs.is_empty() {
+                    let run_id = store.insert_runs(&runs).unwrap_or_else(|e| {
+                        println!("Unable to record missed runs of job {}: {}", key, e);
+                        None
+                    });
+                    status.track_incident(&store, &transition, run_id);
+                    status.notify(&notifier, &transition);
+                    save_state(&store, &key, &status.state);
+                }
+                prune_runs(&store, &key, &status.retention());
+            }
+            jobs.insert(key.clone(), Arc::new(RwLock::new(status)));
         }
 
-        let jobs = Arc::new(jobs);
+        let manager = Self {
+            current: Arc::new(RwLock::new(Jobs {
+                jobs,
+                log_limits,
+                notifier,
+                digest,
+            })),
+            store,
+        };
 
-        let auto_update_jobs_clone = jobs.clone();
+        let auto_update = manager.clone();
         tokio::spawn(async move {
+            let mut digest_checked = SystemTime::now();
             loop {
-                for (_, job) in auto_update_jobs_clone.iter() {
-                    job.write().await.update();
+                let current = auto_update.current().await;
+                let store = &auto_update.store;
+                if let Err(e) = store.heartbeat() {
+                    println!("Unable to store heartbeat: {}", e)
+                }
+                for (key, job) in current.jobs.iter() {
+                    let mut job = job.write().await;
+                    let previous = job.state.status.clone();
+                    let transition = job.update();
+                    let run_id = transition.run.as_ref().and_then(|v| insert_run(store, v));
+                    job.track_incident(store, &transition, run_id);
+                    job.notify(&current.notifier, &transition);
+                    let reminded = job.remind(&current.notifier);
+                    let escalated = job.escalate(&current.notifier);
+                    if job.state.status != previous
+                        || !transition.events.is_empty()
+                        || reminded
+                        || escalated
+                    {
+                        save_state(store, key, &job.state);
+                    }
+                    prune_runs(store, key, &job.retention());
+                }
+                if let Err(e) = store.prune_deliveries(SystemTime::now() - DELIVERY_RETENTION) {
+                    println!("Unable to prune delivery log: {}", e)
+                }
+                if let Err(e) = store.prune_outcomes(SystemTime::now() - OUTCOME_RETENTION) {
+                    println!("Unable to prune run outcomes: {}", e)
+                }
+                if let Some(digest) = &current.digest {
+                    let now = SystemTime::now();
+                    if let Some((from, to)) = digest.due(digest_checked, now) {
+                        let mut jobs: Vec<&String> = current.jobs.keys().collect();
+                        jobs.sort();
+                        match Digest::new(store, &jobs, from, to) {
+                            Ok(v) => current.notifier.send_digest(&digest.notifiers, &v),
+                            Err(e) => println!("Unable to create digest: {}", e),
+                        }
+                    }
+                    digest_checked = now;
                 }
                 sleep(Duration::from_secs(60)).await;
             }
         });
-        Self { jobs }
+        manager
+    }
+
+    async fn current(&self) -> Jobs {
+        self.current.read().await.clone()
     }
 
-    pub async fn update(&self, update: ClientUpdate) -> ConfigResult<()> {
-        let mut job = self
+    async fn job(&self, job_id: &str) -> Option<Arc<RwLock<JobStatus>>> {
+        self.current.read().await.jobs.get(job_id).cloned()
+    }
+
+    /// Switches to the jobs, notifiers and limits of `config`. Jobs that are still configured
+    /// keep their state, changed ones get their new schedule and hooks
+    pub async fn reload(&self, config: &Config) -> ConfigResult<Reconciled> {
+        let notifier = Notifier::new(config, self.store.clone())?;
+        let mut current = self.current.write().await;
+        let mut reconciled = Reconciled::default();
+        let mut jobs = HashMap::new();
+        for (key, job) in config.jobs.iter() {
+            let status = match current.jobs.get(key) {
+                Some(status) => {
+                    let mut locked = status.write().await;
+                    if serialized(&locked.job) != serialized(job) {
+                        reconciled.changed.push(key.clone());
+                    }
+                    // secrets and header values aren't serialized, the job is replaced either way
+                    locked.job = job.clone();
+                    status.clone()
+                }
+                None => {
+                    reconciled.added.push(key.clone());
+                    Arc::new(RwLock::new(load_status(&self.store, key, job.clone())))
+                }
+            };
+            jobs.insert(key.clone(), status);
+        }
+        reconciled.removed = current
+            .jobs
+            .keys()
+            .filter(|v| !config.jobs.contains_key(*v))
+            .cloned()
+            .collect();
+        reconciled.added.sort();
+        reconciled.removed.sort();
+        reconciled.changed.sort();
+        *current = Jobs {
+            jobs,
+            log_limits: config.logs.clone(),
+            notifier,
+            digest: config.digest.clone(),
+        };
+        Ok(reconciled)
+    }
+
+    pub async fn update(&self, mut update: ClientUpdate) -> ConfigResult<()> {
+        let current = self.current().await;
+        if let Update::FinishedJob(output) | Update::Error(output) = &mut update.update {
+            current.log_limits.truncate(&mut output.stdout);
+            current.log_limits.truncate(&mut output.stderr);
+        }
+        let job_id = update.job_id.clone();
+        let mut job = current
             .jobs
-            .get(&update.job_id)
+            .get(&job_id)
             .ok_or(ConfigError::ClientNotFound)?
             .write()
             .await;
-        job.client_update(update);
+        let transition = job.client_update(update);
+        let run_id = transition
+            .run
+            .as_ref()
+            .and_then(|v| insert_run(&self.store, v));
+        job.track_incident(&self.store, &transition, run_id);
+        job.notify(&current.notifier, &transition);
+        job.escalate(&current.notifier);
+        if transition.run.is_some() {
+            prune_runs(&self.store, &job_id, &job.retention());
+        }
+        save_state(&self.store, &job_id, &job.state);
 
         Ok(())
     }
 
-    pub fn get_jobs(&self) -> Vec<&String> {
-        self.jobs.keys().collect()
+    /// `None` if the job doesn't exist, `false` if it has no open problem
+    pub async fn acknowledge(&self, job_id: &str, by: Option<String>) -> Option<bool> {
+        let job = self.job(job_id).await?;
+        let mut job = job.write().await;
+        let first = job
+            .state
+            .problem
+            .as_ref()
+            .is_some_and(|v| v.acknowledged.is_none());
+        if !job.acknowledge(by.clone()) {
+            return Some(false);
+        }
+        save_state(&self.store, job_id, &job.state);
+        if let (true, Some(incident_id)) =
+            (first, job.state.problem.as_ref().and_then(|v| v.incident))
+        {
+            let mut entry = TimelineEntry::new(
+                incident_id,
+                TimelineKind::Acknowledged,
+                "Acknowledged".to_string(),
+            );
+            entry.author = by;
+            incident::record(&self.store, &entry);
+        }
+        Some(true)
+    }
+
+    pub fn get_incident(&self, id: i64) -> StoreResult<Option<IncidentDetails>> {
+        let Some(incident) = self.store.incident(id)? else {
+            return Ok(None);
+        };
+        Ok(Some(IncidentDetails {
+            timeline: self.store.timeline(incident.id)?,
+            incident,
+        }))
+    }
+
+    /// Returns `false` if the incident doesn't exist
+    pub fn comment_incident(&self, entry: &TimelineEntry) -> StoreResult<bool> {
+        if self.store.incident(entry.incident_id)?.is_none() {
+            return Ok(false);
+        }
+        self.store.insert_timeline(entry)?;
+        Ok(true)
+    }
+
+    pub async fn get_incidents(
+        &self,
+        job: &str,
+        page: usize,
+        per_page: usize,
+    ) -> Option<StoreResult<IncidentPage>> {
+        self.job(job).await?;
+        let load = || {
+            Ok(IncidentPage {
+                incidents: self.store.incidents(job, page * per_page, per_page)?,
+                total: self.store.count_incidents(job)?,
+                page,
+                per_page,
+            })
+        };
+        Some(load())
+    }
+
+    pub async fn get_jobs(&self) -> Vec<String> {
+        self.current.read().await.jobs.keys().cloned().collect()
     }
 
     pub async fn get_job(&self, job: &str) -> Option<JobStatus> {
-        match self.jobs.get(job) {
+        match self.job(job).await {
             None => None,
             Some(v) => Some(v.read().await.clone()),
         }
     }
+
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    pub async fn get_metrics(&self) -> Vec<JobMetrics> {
+        let mut metrics = Vec::new();
+        for job in self.current().await.jobs.values() {
+            metrics.push(job.read().await.metrics());
+        }
+        metrics
+    }
+
+    pub async fn get_stats(&self, job: &str) -> Option<StoreResult<Vec<JobStats>>> {
+        let job = self.job(job).await?.read().await.job.clone();
+        let load = || {
+            let now = SystemTime::now();
+            let mut stats = Vec::new();
+            for (name, window) in WINDOWS {
+                let runs = self.store.runs_since(&job.id, now - window)?;
+                stats.push(JobStats::new(
+                    name,
+                    &runs,
+                    &job.execution_time,
+                    Duration::from_secs(job.grace),
+                ));
+            }
+            Ok(stats)
+        };
+        Some(load())
+    }
+
+    pub async fn get_history(
+        &self,
+        job: &str,
+        page: usize,
+        per_page: usize,
+    ) -> Option<StoreResult<HistoryPage>> {
+        self.job(job).await?;
+        let load = || {
+            Ok(HistoryPage {
+                runs: self.store.runs(job, page * per_page, per_page)?,
+                total: self.store.count_runs(job)?,
+                page,
+                per_page,
+            })
+        };
+        Some(load())
+    }
+}
+
+/// A configured job with the state it had when the server last saw it
+fn load_status(store: &Store, job_id: &str, job: Job) -> JobStatus {
+    let state = match store.load_state(job_id) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => {
+            println!("Unable to restore state of job {}: {}", job_id, e);
+            JobState::default()
+        }
+    };
+    JobStatus::new(job, state)
+}
+
+/// Jobs hold hooks and templates that can't be compared, their serializations can
+fn serialized(job: &Job) -> Option<String> {
+    serde_json::to_string(job).ok()
+}
+
+fn save_state(store: &Store, job_id: &str, state: &JobState) {
+    if let Err(e) = store.save_state(job_id, state) {
+        println!("Unable to persist state of job {}: {}", job_id, e)
+    }
+}
+
+/// Returns the id of the stored run
+fn insert_run(store: &Store, run: &Run) -> Option<i64> {
+    match store.insert_run(run) {
+        Ok(id) => Some(id),
+        Err(e) => {
+            println!("Unable to record run of job {}: {}", run.job_id, e);
+            None
+        }
+    }
+}
+
+fn prune_runs(store: &Store, job_id: &str, retention: &HistoryRetention) {
+    if let Err(e) = store.prune_runs(job_id, retention) {
+        println!("Unable to prune history of job {}: {}", job_id, e)
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -240,14 +1137,300 @@ pub struct ClientUpdate {
 #[derive(Deserialize, Debug)]
 enum Update {
     StartingJob,
-    FinishedJob(String),
-    Error(String),
+    FinishedJob(#[serde(deserialize_with = "output_or_stdout")] JobOutput),
+    Error(#[serde(deserialize_with = "output_or_stderr")] JobOutput),
+}
+
+/// Clients from before the output was split report a finished job with its stdout
+fn output_or_stdout<'de, D>(deserializer: D) -> Result<JobOutput, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(match ReportedOutput::deserialize(deserializer)? {
+        ReportedOutput::Output(v) => v,
+        ReportedOutput::Text(stdout) => JobOutput::from_text(stdout, String::new()),
+    })
 }
 
-fn call_hook(hook: Url) {
-    tokio::spawn(async move {
-        if let Err(e) = reqwest::get(hook).await {
-            println!("Error calling hook: {}", e)
+/// and a failed one with its stderr
+fn output_or_stderr<'de, D>(deserializer: D) -> Result<JobOutput, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(match ReportedOutput::deserialize(deserializer)? {
+        ReportedOutput::Output(v) => v,
+        ReportedOutput::Text(stderr) => JobOutput::from_text(String::new(), stderr),
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ReportedOutput {
+    Output(JobOutput),
+    Text(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobOutput {
+    stdout: String,
+    stderr: String,
+    exit_code: Option<i32>,
+    /// The signal that terminated the process, if any
+    signal: Option<i32>,
+}
+
+impl JobOutput {
+    fn from_text(stdout: String, stderr: String) -> Self {
+        Self {
+            stdout,
+            stderr,
+            exit_code: None,
+            signal: None,
         }
-    });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        serde::de::value::{Error, StrDeserializer},
+        std::time::UNIX_EPOCH,
+    };
+
+    fn schedule(v: &str) -> CronExecutionTime {
+        CronExecutionTime::deserialize(StrDeserializer::<Error>::new(v)).unwrap()
+    }
+
+    fn time(v: &str) -> DateTime<Utc> {
+        v.parse().unwrap()
+    }
+
+    fn update(json: &str) -> Update {
+        serde_json::from_str::<ClientUpdate>(json).unwrap().update
+    }
+
+    fn status(job: &str) -> JobStatus {
+        JobStatus::new(toml::from_str(job).unwrap(), JobState::default())
+    }
+
+    fn report(update: &str) -> ClientUpdate {
+        let report = format!(
+            r#"{{"job_id": "backup", "hostname": "h", "command": "c", "update": {}}}"#,
+            update
+        );
+        serde_json::from_str(&report).unwrap()
+    }
+
+    fn finish(status: &mut JobStatus, update: &str) -> Run {
+        status.client_update(report(r#""StartingJob""#));
+        status.client_update(report(update)).run.unwrap()
+    }
+
+    #[test]
+    fn fails_on_exit_code_or_signal() {
+        let mut status = status(r#"execution_time = "* * * * *""#);
+        let run = finish(
+            &mut status,
+            r#"{"Error": {"stdout": "", "stderr": "", "exit_code": 1, "signal": null}}"#,
+        );
+        assert_eq!(run.outcome, RunOutcome::Failed);
+        assert_eq!(run.failure.as_deref(), Some("Exited with code 1"));
+        let run = finish(
+            &mut status,
+            r#"{"Error": {"stdout": "", "stderr": "", "exit_code": null, "signal": 9}}"#,
+        );
+        assert_eq!(run.failure.as_deref(), Some("Killed by signal 9"));
+        assert_eq!(run.signal, Some(9));
+        assert_eq!((status.state.runs, status.state.failures), (2, 2));
+    }
+
+    #[test]
+    fn fails_on_assertions_despite_exit_code_0() {
+        let mut status = status(
+            r#"
+            execution_time = "* * * * *"
+            assertions = {must_not_match = ["ERROR"], stream = "stderr"}
+            "#,
+        );
+        let run = finish(
+            &mut status,
+            r#"{"FinishedJob": {"stdout": "ERROR", "stderr": "", "exit_code": 0, "signal": null}}"#,
+        );
+        assert_eq!(run.outcome, RunOutcome::Succeeded);
+        assert_eq!(run.failure, None);
+        let run = finish(
+            &mut status,
+            r#"{"FinishedJob": {"stdout": "", "stderr": "ERROR", "exit_code": 0, "signal": null}}"#,
+        );
+        assert_eq!(run.outcome, RunOutcome::Failed);
+        assert_eq!(
+            run.failure.as_deref(),
+            Some("Output matched 'ERROR': ERROR")
+        );
+        assert!(matches!(status.state.status, Status::ClientError));
+    }
+
+    fn problem(since: SystemTime) -> Problem {
+        let mut state = JobState::default();
+        state.open_problem(EventKind::Failed);
+        let mut problem = state.problem.unwrap();
+        problem.since = since;
+        problem
+    }
+
+    #[test]
+    fn dedup_alarms() {
+        let mut problem = problem(UNIX_EPOCH);
+        let time = UNIX_EPOCH + Duration::from_secs(60);
+        assert!(problem.alarm(String::from("hooks.0"), true, time));
+        assert!(!problem.alarm(String::from("hooks.0"), true, time));
+        assert!(problem.alarm(String::from("notifiers.ops"), true, time));
+        // without dedup every alarm goes out
+        assert!(problem.alarm(String::from("hooks.1"), false, time));
+        assert!(problem.alarm(String::from("hooks.1"), false, time));
+    }
+
+    #[test]
+    fn reminds_after_the_interval() {
+        let mut problem = problem(UNIX_EPOCH);
+        let key = || String::from("hooks.0");
+        let after = |v| UNIX_EPOCH + Duration::from_secs(v);
+        let interval = Duration::from_secs(600);
+        // counted from the start of the problem until the hook is told
+        assert!(!problem.remind(key(), interval, after(599)));
+        assert!(problem.remind(key(), interval, after(600)));
+        // then from the last reminder
+        assert!(!problem.remind(key(), interval, after(1100)));
+        assert!(problem.remind(key(), interval, after(1200)));
+        // and from the last alarm
+        problem.alarm(key(), false, after(1500));
+        assert!(!problem.remind(key(), interval, after(1800)));
+        assert!(problem.remind(key(), interval, after(2100)));
+        // a clock that went back doesn't remind
+        assert!(!problem.remind(key(), interval, after(0)));
+    }
+
+    #[test]
+    fn reads_output() {
+        let Update::Error(output) = update(
+            r#"{"job_id": "a", "hostname": "h", "command": "c", "update": {"Error":
+            {"stdout": "out", "stderr": "err", "exit_code": 2, "signal": null}}}"#,
+        ) else {
+            panic!("not an error")
+        };
+        assert_eq!(
+            (
+                output.stdout.as_str(),
+                output.stderr.as_str(),
+                output.exit_code
+            ),
+            ("out", "err", Some(2))
+        );
+    }
+
+    #[test]
+    fn reads_output_of_old_clients() {
+        let Update::FinishedJob(output) = update(
+            r#"{"job_id": "a", "hostname": "h", "command": "c", "update": {"FinishedJob": "done"}}"#,
+        ) else {
+            panic!("not a finished job")
+        };
+        assert_eq!(
+            (output.stdout.as_str(), output.stderr.as_str()),
+            ("done", "")
+        );
+        let Update::Error(output) = update(
+            r#"{"job_id": "a", "hostname": "h", "command": "c", "update": {"Error": "failed"}}"#,
+        ) else {
+            panic!("not an error")
+        };
+        assert_eq!(
+            (output.stdout.as_str(), output.stderr.as_str()),
+            ("", "failed")
+        );
+        assert_eq!(output.exit_code, None);
+    }
+
+    #[test]
+    fn weekday_without_day() {
+        let schedule = schedule("0 8 * * mon");
+        // 2024-01-01 was a monday
+        assert!(schedule.matches(time("2024-01-01T08:00:00Z")));
+        assert!(!schedule.matches(time("2024-01-02T08:00:00Z")));
+        assert_eq!(
+            schedule.next_after(time("2024-01-01T08:00:00Z")),
+            Some(time("2024-01-08T08:00:00Z"))
+        );
+        assert_eq!(
+            schedule.previous_before(time("2024-01-08T08:00:00Z")),
+            Some(time("2024-01-01T08:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn day_without_weekday() {
+        let schedule = schedule("0 8 15 * *");
+        assert!(schedule.matches(time("2024-01-15T08:00:00Z")));
+        assert!(!schedule.matches(time("2024-01-16T08:00:00Z")));
+    }
+
+    #[test]
+    fn day_or_weekday() {
+        let schedule = schedule("0 8 15 * sun");
+        // a monday, a sunday and neither
+        assert!(schedule.matches(time("2024-01-15T08:00:00Z")));
+        assert!(schedule.matches(time("2024-01-07T08:00:00Z")));
+        assert!(!schedule.matches(time("2024-01-16T08:00:00Z")));
+    }
+
+    #[test]
+    fn next_run_on_the_next_day() {
+        assert_eq!(
+            schedule("30 2 * * *").next_after(time("2024-01-01T03:00:00Z")),
+            Some(time("2024-01-02T02:30:00Z"))
+        );
+        assert_eq!(
+            schedule("30 2 * * *").next_after(time("2024-01-01T02:29:59Z")),
+            Some(time("2024-01-01T02:30:00Z"))
+        );
+    }
+
+    #[test]
+    fn next_run_on_a_weekday() {
+        // from a saturday
+        assert_eq!(
+            schedule("0 9 * * mon").next_after(time("2024-01-06T12:00:00Z")),
+            Some(time("2024-01-08T09:00:00Z"))
+        );
+        assert_eq!(
+            schedule("0 9 * * 0").next_after(time("2024-01-06T12:00:00Z")),
+            Some(time("2024-01-07T09:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn next_run_on_a_day_or_weekday() {
+        // the 15th is a monday, the sunday before comes first
+        assert_eq!(
+            schedule("0 8 15 * sun").next_after(time("2024-01-08T00:00:00Z")),
+            Some(time("2024-01-14T08:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn next_run_in_the_next_year() {
+        assert_eq!(
+            schedule("0 0 1 1 *").next_after(time("2024-01-01T00:00:00Z")),
+            Some(time("2025-01-01T00:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn no_next_run_after_reboot() {
+        assert_eq!(
+            schedule("@reboot").next_after(time("2024-01-01T00:00:00Z")),
+            None
+        );
+    }
 }
@@ -0,0 +1,329 @@
+use {
+    crate::{
+        cron::CronExecutionTime,
+        error::StoreResult,
+        export::format_time,
+        history::RunOutcome,
+        notifier::{serialize_time, EventKind},
+        store::Store,
+    },
+    serde::{Deserialize, Serialize},
+    std::time::{Duration, SystemTime},
+};
+
+/// How far back the usual duration of a job is taken from
+const BASELINE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Runs a job needs in the baseline before its durations are called unusual
+const MIN_SAMPLES: usize = 5;
+
+/// A run is unusual if it took this many times longer or shorter than the median
+const FACTOR: f64 = 2.0;
+
+/// and differs from the median by at least this much, so quick jobs don't show up for every hiccup
+const MIN_DIFFERENCE: Duration = Duration::from_secs(10);
+
+/// `[digest]`, a summary of every job sent to `notifiers` on `schedule`
+#[derive(Deserialize, Debug, Clone)]
+pub struct DigestConfig {
+    pub schedule: CronExecutionTime,
+    pub notifiers: Vec<String>,
+}
+
+/// What happened to every job between two digests
+#[derive(Serialize, Debug, Clone)]
+pub struct Digest {
+    /// Always [EventKind::Digest], tells digests apart from job events
+    pub event: EventKind,
+    pub title: String,
+    /// The whole digest as plain text
+    pub text: String,
+    #[serde(serialize_with = "serialize_time")]
+    pub from: SystemTime,
+    #[serde(serialize_with = "serialize_time")]
+    pub to: SystemTime,
+    pub jobs: Vec<JobDigest>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct JobDigest {
+    pub job_id: String,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub missed: usize,
+    /// Why the last failed run failed
+    pub last_failure: Option<String>,
+    pub unusual_durations: Vec<UnusualDuration>,
+}
+
+/// A run that took much longer or shorter than the job usually takes
+#[derive(Serialize, Debug, Clone)]
+pub struct UnusualDuration {
+    #[serde(serialize_with = "serialize_time")]
+    pub ended: SystemTime,
+    pub seconds: f64,
+    /// Median of the runs in the last 30 days
+    pub usual_seconds: f64,
+}
+
+impl DigestConfig {
+    /// The period of the digest if it was scheduled in `(checked, now]`. It starts when the
+    /// previous digest was scheduled
+    pub fn due(&self, checked: SystemTime, now: SystemTime) -> Option<(SystemTime, SystemTime)> {
+        let to = self
+            .schedule
+            .scheduled_between(checked.into(), now.into())
+            .pop()?;
+        let from = self
+            .schedule
+            .previous_before(to)
+            .unwrap_or(to - chrono::Duration::days(1));
+        Some((from.into(), to.into()))
+    }
+}
+
+impl JobDigest {
+    fn runs(&self) -> usize {
+        self.succeeded + self.failed
+    }
+}
+
+impl Digest {
+    /// Summarizes the runs that ended in `(from, to]`
+    pub fn new(
+        store: &Store,
+        jobs: &[&String],
+        from: SystemTime,
+        to: SystemTime,
+    ) -> StoreResult<Self> {
+        let mut digests = Vec::new();
+        for job_id in jobs.iter() {
+            let stored = store.runs_since(job_id, from.min(to - BASELINE))?;
+            let mut durations: Vec<Duration> = stored
+                .iter()
+                .filter(|v| v.outcome != RunOutcome::Missed && v.ended >= to - BASELINE)
+                .filter_map(|v| v.duration)
+                .collect();
+            durations.sort();
+            let usual = match durations.len() {
+                len if len < MIN_SAMPLES => None,
+                len => Some(durations[len / 2]),
+            };
+            let runs: Vec<_> = stored
+                .into_iter()
+                .filter(|v| v.ended > from && v.ended <= to)
+                .collect();
+            let count = |outcome| runs.iter().filter(|v| v.outcome == outcome).count();
+            let unusual_durations = runs
+                .iter()
+                .filter_map(|run| {
+                    let (duration, usual) = (run.duration?, usual?);
+                    let seconds = duration.as_secs_f64();
+                    let differs = seconds > usual.as_secs_f64() * FACTOR
+                        || seconds < usual.as_secs_f64() / FACTOR;
+                    let difference = duration.max(usual) - duration.min(usual);
+                    (differs && difference >= MIN_DIFFERENCE).then_some(UnusualDuration {
+                        ended: run.ended,
+                        seconds,
+                        usual_seconds: usual.as_secs_f64(),
+                    })
+                })
+                .collect();
+            digests.push(JobDigest {
+                job_id: job_id.to_string(),
+                succeeded: count(RunOutcome::Succeeded),
+                failed: count(RunOutcome::Failed),
+                missed: count(RunOutcome::Missed),
+                last_failure: runs
+                    .iter()
+                    .filter(|v| v.outcome == RunOutcome::Failed)
+                    .max_by_key(|v| v.ended)
+                    .and_then(|v| v.failure.clone()),
+                unusual_durations,
+            });
+        }
+        let mut digest = Self {
+            event: EventKind::Digest,
+            title: String::new(),
+            text: String::new(),
+            from,
+            to,
+            jobs: digests,
+        };
+        digest.title = digest.render_title();
+        digest.text = digest.render_text();
+        Ok(digest)
+    }
+
+    fn render_title(&self) -> String {
+        let sum = |count: fn(&JobDigest) -> usize| self.jobs.iter().map(count).sum::<usize>();
+        format!(
+            "Digest: {} failed, {} missed, {} succeeded",
+            sum(|v| v.failed),
+            sum(|v| v.missed),
+            sum(|v| v.succeeded)
+        )
+    }
+
+    fn render_text(&self) -> String {
+        let mut text = format!(
+            "From {} to {}\n",
+            format_time(self.from),
+            format_time(self.to)
+        );
+        let mut section = |title: &str, lines: Vec<String>| {
+            if !lines.is_empty() {
+                text.push_str(&format!("\n{}:\n", title));
+                for line in lines {
+                    text.push_str(&format!("  {}\n", line));
+                }
+            }
+        };
+        section(
+            "Failed",
+            self.jobs
+                .iter()
+                .filter(|v| v.failed > 0)
+                .map(|v| match &v.last_failure {
+                    Some(reason) => format!(
+                        "{}: {} of {} runs, last: {}",
+                        v.job_id,
+                        v.failed,
+                        v.runs(),
+                        reason
+                    ),
+                    None => format!("{}: {} of {} runs", v.job_id, v.failed, v.runs()),
+                })
+                .collect(),
+        );
+        section(
+            "Missed",
+            self.jobs
+                .iter()
+                .filter(|v| v.missed > 0)
+                .map(|v| format!("{}: {} missed", v.job_id, v.missed))
+                .collect(),
+        );
+        section(
+            "Unusual durations",
+            self.jobs
+                .iter()
+                .flat_map(|job| {
+                    job.unusual_durations.iter().map(|v| {
+                        format!(
+                            "{}: {:.1}s, ended {}, usually {:.1}s",
+                            job.job_id,
+                            v.seconds,
+                            format_time(v.ended),
+                            v.usual_seconds
+                        )
+                    })
+                })
+                .collect(),
+        );
+        section(
+            "Ran",
+            self.jobs
+                .iter()
+                .filter(|v| v.runs() > 0)
+                .map(|v| {
+                    format!(
+                        "{}: {} succeeded, {} failed",
+                        v.job_id, v.succeeded, v.failed
+                    )
+                })
+                .collect(),
+        );
+        section(
+            "Did not run",
+            self.jobs
+                .iter()
+                .filter(|v| v.runs() == 0)
+                .map(|v| v.job_id.clone())
+                .collect(),
+        );
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::history::Run,
+        chrono::{DateTime, Utc},
+        std::{path::Path, time::UNIX_EPOCH},
+    };
+
+    fn time(v: &str) -> SystemTime {
+        v.parse::<DateTime<Utc>>().unwrap().into()
+    }
+
+    #[test]
+    fn due_once_scheduled() {
+        let config: DigestConfig =
+            toml::from_str("schedule = \"0 8 * * *\"\nnotifiers = [\"ops\"]").unwrap();
+        let checked = time("2024-03-04T07:00:00Z");
+        assert_eq!(config.due(checked, time("2024-03-04T07:59:00Z")), None);
+        assert_eq!(
+            config.due(checked, time("2024-03-04T08:00:30Z")),
+            Some((time("2024-03-03T08:00:00Z"), time("2024-03-04T08:00:00Z")))
+        );
+        assert_eq!(
+            config.due(time("2024-03-04T08:01:00Z"), time("2024-03-04T09:00:00Z")),
+            None
+        );
+        // after a downtime only the last digest is sent
+        assert_eq!(
+            config.due(checked, time("2024-03-07T12:00:00Z")),
+            Some((time("2024-03-06T08:00:00Z"), time("2024-03-07T08:00:00Z")))
+        );
+    }
+
+    /// Stores a run of `job_id` that took `seconds` and ended `days_ago` before `to`
+    fn insert(store: &Store, job_id: &str, to: SystemTime, days_ago: u64, seconds: u64) {
+        let ended = to - Duration::from_secs(days_ago * 24 * 60 * 60 + 60);
+        let mut run = Run::new(job_id, RunOutcome::Succeeded, ended);
+        run.started = Some(ended - Duration::from_secs(seconds));
+        store.insert_run(&run).unwrap();
+    }
+
+    #[test]
+    fn unusual_durations() {
+        let store = Store::open(Path::new(":memory:")).unwrap();
+        let to = UNIX_EPOCH + Duration::from_secs(100 * 24 * 60 * 60);
+        let (backup, quick, new) = (
+            String::from("backup"),
+            String::from("quick"),
+            String::from("new"),
+        );
+        for days_ago in 2..7 {
+            insert(&store, &backup, to, days_ago, 60);
+            insert(&store, &quick, to, days_ago, 2);
+        }
+        for seconds in [200, 100, 25] {
+            insert(&store, &backup, to, 0, seconds);
+        }
+        // three times as long, but only by 4 seconds
+        insert(&store, &quick, to, 0, 6);
+        for seconds in [1, 1, 1, 1000] {
+            insert(&store, &new, to, 0, seconds);
+        }
+
+        let digest = Digest::new(
+            &store,
+            &[&backup, &quick, &new],
+            to - Duration::from_secs(24 * 60 * 60),
+            to,
+        )
+        .unwrap();
+        let unusual: Vec<Vec<f64>> = digest
+            .jobs
+            .iter()
+            .map(|v| v.unusual_durations.iter().map(|v| v.seconds).collect())
+            .collect();
+        assert_eq!(unusual, [vec![200.0, 25.0], vec![], vec![]]);
+        assert_eq!(digest.jobs[0].unusual_durations[0].usual_seconds, 60.0);
+        assert_eq!(digest.jobs[0].succeeded, 3);
+    }
+}
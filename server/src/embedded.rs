@@ -0,0 +1,93 @@
+use {
+    rocket::{
+        get,
+        http::{ContentType, Header, Status},
+        request::{FromRequest, Outcome, Request},
+        response::{self, Responder, Response},
+    },
+    std::{io::Cursor, path::PathBuf},
+};
+
+include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+
+/// Files whose name holds a hash of their content never change
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// index.html and the copied files keep their name, browsers have to check if they changed
+const REVALIDATE: &str = "no-cache";
+
+/// A file of the dashboard, built into the binary by the `embed-frontend` feature
+pub struct Asset {
+    path: &'static str,
+    etag: &'static str,
+    immutable: bool,
+    content: &'static [u8],
+}
+
+pub struct IfNoneMatch(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IfNoneMatch(
+            request.headers().get_one("If-None-Match").map(String::from),
+        ))
+    }
+}
+
+pub struct Served {
+    asset: &'static Asset,
+    /// Whether the browser already has this version
+    cached: bool,
+}
+
+impl<'r> Responder<'r, 'static> for Served {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .header(Header::new("ETag", self.asset.etag))
+            .header(Header::new(
+                "Cache-Control",
+                match self.asset.immutable {
+                    true => IMMUTABLE,
+                    false => REVALIDATE,
+                },
+            ));
+        if self.cached {
+            return response.status(Status::NotModified).ok();
+        }
+        let content_type = self
+            .asset
+            .path
+            .rsplit_once('.')
+            .and_then(|(_, extension)| ContentType::from_extension(extension))
+            .unwrap_or(ContentType::Binary);
+        response
+            .header(content_type)
+            .sized_body(self.asset.content.len(), Cursor::new(self.asset.content))
+            .ok()
+    }
+}
+
+/// Serves the dashboard from memory, ranked like a `FileServer` so the api routes come first
+#[get("/<path..>", rank = 10)]
+pub fn asset(path: PathBuf, if_none_match: IfNoneMatch) -> Option<Served> {
+    let path = path
+        .components()
+        .map(|v| v.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?
+        .join("/");
+    let path = match path.as_str() {
+        "" => "index.html",
+        path => path,
+    };
+    let asset = ASSETS.iter().find(|v| v.path == path)?;
+    Some(Served {
+        asset,
+        cached: if_none_match
+            .0
+            .is_some_and(|v| v.split(',').any(|v| v.trim() == asset.etag)),
+    })
+}
@@ -7,6 +7,8 @@ pub enum ConfigError {
     ReadFileError(std::io::Error),
     TomlParseError(toml::de::Error),
     ClientNotFound,
+    SmtpError(lettre::transport::smtp::Error),
+    InvalidConfig(String),
 }
 
 impl Error for ConfigError {}
@@ -23,6 +25,12 @@ impl fmt::Display for ConfigError {
             ConfigError::ClientNotFound => {
                 write!(f, "Client was not found in config!")
             }
+            ConfigError::SmtpError(v) => {
+                write!(f, "Invalid smtp settings: {}", v)
+            }
+            ConfigError::InvalidConfig(v) => {
+                write!(f, "Invalid config: {}", v)
+            }
         }
     }
 }
@@ -38,3 +46,44 @@ impl From<toml::de::Error> for ConfigError {
         ConfigError::TomlParseError(value)
     }
 }
+
+impl From<lettre::transport::smtp::Error> for ConfigError {
+    fn from(value: lettre::transport::smtp::Error) -> Self {
+        ConfigError::SmtpError(value)
+    }
+}
+
+pub type StoreResult<T> = Result<T, StoreError>;
+
+#[derive(Debug)]
+pub enum StoreError {
+    DatabaseError(rusqlite::Error),
+    SerializationError(serde_json::Error),
+}
+
+impl Error for StoreError {}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::DatabaseError(v) => {
+                write!(f, "Database error: {}", v)
+            }
+            StoreError::SerializationError(v) => {
+                write!(f, "Unable to (de)serialize stored job state: {}", v)
+            }
+        }
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(value: rusqlite::Error) -> Self {
+        StoreError::DatabaseError(value)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(value: serde_json::Error) -> Self {
+        StoreError::SerializationError(value)
+    }
+}
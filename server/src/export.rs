@@ -0,0 +1,262 @@
+use {
+    crate::{error::StoreResult, history::Run, store::Store},
+    chrono::{DateTime, NaiveDate, SecondsFormat, Utc},
+    clap::{Args, ValueEnum},
+    rocket::http::ContentType,
+    serde::{Deserialize, Serialize},
+    std::{io::Write, time::SystemTime},
+};
+
+const BATCH_SIZE: usize = 100;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Ndjson,
+}
+
+#[derive(Deserialize, Debug, Clone, Args)]
+pub struct ExportQuery {
+    /// Comma separated jobs to export, every job if omitted
+    #[arg(short, long, value_delimiter = ',')]
+    #[serde(default)]
+    pub jobs: Vec<String>,
+    /// Only runs that ended at or after this time (RFC 3339 or YYYY-MM-DD)
+    #[arg(long, value_parser = parse_time)]
+    pub from: Option<DateTime<Utc>>,
+    /// Only runs that ended before this time (RFC 3339 or YYYY-MM-DD)
+    #[arg(long, value_parser = parse_time)]
+    pub to: Option<DateTime<Utc>>,
+    #[arg(short, long, value_enum, default_value_t)]
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(v) = DateTime::parse_from_rfc3339(value) {
+        return Ok(v.into());
+    }
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(v) => Ok(v.and_hms_opt(0, 0, 0).unwrap().and_utc()),
+        Err(_) => Err(String::from("expected an RFC 3339 time or YYYY-MM-DD")),
+    }
+}
+
+#[derive(Serialize)]
+struct ExportRecord<'a> {
+    id: i64,
+    job_id: &'a str,
+    outcome: &'a str,
+    started: Option<String>,
+    ended: String,
+    duration_seconds: Option<f64>,
+    exit_code: Option<i32>,
+    signal: Option<i32>,
+    hostname: Option<&'a str>,
+    command: Option<&'a str>,
+    stdout: Option<&'a str>,
+    stderr: Option<&'a str>,
+    failure: Option<&'a str>,
+}
+
+impl<'a> From<&'a Run> for ExportRecord<'a> {
+    fn from(run: &'a Run) -> Self {
+        Self {
+            id: run.id,
+            job_id: &run.job_id,
+            outcome: run.outcome.as_str(),
+            started: run.started.map(format_time),
+            ended: format_time(run.ended),
+            duration_seconds: run.duration.map(|v| v.as_secs_f64()),
+            exit_code: run.exit_code,
+            signal: run.signal,
+            hostname: run.hostname.as_deref(),
+            command: run.command.as_deref(),
+            stdout: run.stdout.as_deref(),
+            stderr: run.stderr.as_deref(),
+            failure: run.failure.as_deref(),
+        }
+    }
+}
+
+pub fn format_time(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            ExportFormat::Csv => ContentType::CSV,
+            ExportFormat::Ndjson => ContentType::new("application", "x-ndjson"),
+        }
+    }
+
+    pub fn header(&self) -> Option<String> {
+        match self {
+            ExportFormat::Csv => Some(String::from(
+                "id,job_id,outcome,started,ended,duration_seconds,exit_code,signal,hostname,command,stdout,stderr,failure\n",
+            )),
+            ExportFormat::Ndjson => None,
+        }
+    }
+
+    /// One line per run, including the line break
+    pub fn render(&self, run: &Run) -> String {
+        let record = ExportRecord::from(run);
+        match self {
+            ExportFormat::Csv => {
+                let optional = |v: Option<String>| v.unwrap_or_default();
+                let fields = [
+                    record.id.to_string(),
+                    record.job_id.to_string(),
+                    record.outcome.to_string(),
+                    optional(record.started),
+                    record.ended,
+                    optional(record.duration_seconds.map(|v| v.to_string())),
+                    optional(record.exit_code.map(|v| v.to_string())),
+                    optional(record.signal.map(|v| v.to_string())),
+                    optional(record.hostname.map(String::from)),
+                    optional(record.command.map(String::from)),
+                    optional(record.stdout.map(String::from)),
+                    optional(record.stderr.map(String::from)),
+                    optional(record.failure.map(String::from)),
+                ];
+                let mut line = fields
+                    .iter()
+                    .map(|v| escape_csv(v))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                line.push('\n');
+                line
+            }
+            ExportFormat::Ndjson => {
+                // serializing a struct of strings and numbers can't fail
+                let mut line = serde_json::to_string(&record).unwrap();
+                line.push('\n');
+                line
+            }
+        }
+    }
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Walks through the history of the exported jobs in batches
+pub struct ExportCursor {
+    jobs: Vec<String>,
+    from: Option<SystemTime>,
+    to: Option<SystemTime>,
+    job_index: usize,
+    after_id: i64,
+}
+
+impl ExportCursor {
+    /// `all_jobs` is used if the query doesn't name any jobs
+    pub fn new(query: &ExportQuery, all_jobs: Vec<String>) -> Self {
+        let mut jobs = match query.jobs.is_empty() {
+            true => all_jobs,
+            false => query.jobs.clone(),
+        };
+        jobs.sort();
+        jobs.dedup();
+        Self {
+            jobs,
+            from: query.from.map(SystemTime::from),
+            to: query.to.map(SystemTime::from),
+            job_index: 0,
+            after_id: 0,
+        }
+    }
+
+    /// Returns `None` once every job was exported
+    pub fn next_batch(&mut self, store: &Store) -> StoreResult<Option<Vec<Run>>> {
+        while let Some(job_id) = self.jobs.get(self.job_index) {
+            let runs = store.export_runs(job_id, self.from, self.to, self.after_id, BATCH_SIZE)?;
+            match runs.last() {
+                Some(v) => {
+                    self.after_id = v.id;
+                    return Ok(Some(runs));
+                }
+                None => {
+                    self.job_index += 1;
+                    self.after_id = 0;
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Writes the export to `out`, used by the `export` subcommand
+pub fn write(
+    store: &Store,
+    query: &ExportQuery,
+    all_jobs: Vec<String>,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(v) = query.format.header() {
+        out.write_all(v.as_bytes())?;
+    }
+    let mut cursor = ExportCursor::new(query, all_jobs);
+    while let Some(runs) = cursor.next_batch(store)? {
+        for run in runs.iter() {
+            out.write_all(query.format.render(run).as_bytes())?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::history::RunOutcome,
+        std::time::{Duration, UNIX_EPOCH},
+    };
+
+    #[test]
+    fn escapes_csv() {
+        assert_eq!(escape_csv("plain text"), "plain text");
+        assert_eq!(escape_csv("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_csv("two\nlines"), "\"two\nlines\"");
+        assert_eq!(escape_csv("crlf\r"), "\"crlf\r\"");
+        assert_eq!(escape_csv(""), "");
+    }
+
+    #[test]
+    fn renders_csv() {
+        let mut run = Run::new(
+            "backup",
+            RunOutcome::Failed,
+            UNIX_EPOCH + Duration::from_secs(60),
+        );
+        run.id = 7;
+        run.exit_code = Some(1);
+        run.command = Some(String::from("tar -czf \"a b.tgz\" ."));
+        run.stderr = Some(String::from("error,\nline"));
+        let header = ExportFormat::Csv.header().unwrap();
+        let line = ExportFormat::Csv.render(&run);
+        assert_eq!(
+            line,
+            "7,backup,Failed,,1970-01-01T00:01:00.000Z,,1,,,\"tar -czf \"\"a b.tgz\"\" .\",,\"error,\nline\",\n"
+        );
+        // every record has as many columns as the header, once quoted fields are skipped
+        let columns = |v: &str| {
+            v.split('"')
+                .step_by(2)
+                .map(|v| v.matches(',').count())
+                .sum::<usize>()
+        };
+        assert_eq!(columns(&line), columns(&header));
+    }
+}
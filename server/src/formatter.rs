@@ -0,0 +1,330 @@
+use {
+    crate::{
+        digest::Digest,
+        export::format_time,
+        logs,
+        notifier::{Event, EventKind},
+    },
+    serde_json::{json, Value},
+};
+
+/// Bytes of the log shown in chat messages, chat systems limit the message size
+const SNIPPET: usize = 1000;
+
+/// Bytes of a digest shown in Slack and Discord messages
+const DIGEST: usize = 3000;
+
+/// Characters Discord accepts in parts of an embed, longer embeds are rejected
+const DISCORD_TITLE: usize = 256;
+const DISCORD_DESCRIPTION: usize = 4096;
+const DISCORD_FIELD: usize = 1024;
+
+impl EventKind {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            EventKind::Started => "started",
+            EventKind::Succeeded => "succeeded",
+            EventKind::Missed => "missed its run",
+            EventKind::Failed => "failed",
+            EventKind::TimedOut => "timed out",
+            EventKind::Recovered => "recovered",
+            EventKind::Digest => "digest",
+        }
+    }
+
+    /// RGB color used to highlight the message
+    fn color(&self) -> u32 {
+        match self {
+            EventKind::Started | EventKind::Digest => 0x1d9bd1,
+            EventKind::Succeeded | EventKind::Recovered => 0x2eb67d,
+            EventKind::TimedOut => 0xecb22e,
+            EventKind::Missed | EventKind::Failed => 0xe01e5a,
+        }
+    }
+}
+
+impl Event {
+    pub fn title(&self) -> String {
+        match self.reminder {
+            true => format!("Reminder: job {} {}", self.job_id, self.event.describe()),
+            false => format!("Job {} {}", self.job_id, self.event.describe()),
+        }
+    }
+
+    /// Name and value of every detail that is known
+    pub fn facts(&self) -> Vec<(&'static str, String)> {
+        let mut facts = vec![("Schedule", self.schedule.clone())];
+        if let Some(v) = &self.hostname {
+            facts.push(("Host", v.clone()));
+        }
+        if let Some(v) = &self.command {
+            facts.push(("Command", v.clone()));
+        }
+        if let Some(v) = &self.failure {
+            facts.push(("Reason", v.clone()));
+        }
+        if let Some(v) = self.duration {
+            facts.push(("Duration", format!("{:.1}s", v.as_secs_f64())));
+        }
+        if let Some(v) = self.escalation_step {
+            facts.push(("Escalation", format!("step {}", v)));
+        }
+        facts
+    }
+
+    /// The end of stderr, or of stdout if stderr is empty
+    pub fn snippet(&self) -> Option<&str> {
+        [&self.stderr, &self.stdout]
+            .into_iter()
+            .filter_map(|v| v.as_deref())
+            .find(|v| !v.trim().is_empty())
+            .map(|v| logs::tail(v, SNIPPET))
+    }
+}
+
+pub fn slack(event: &Event) -> Value {
+    let mut text = format!("*{}*", escape_slack(&event.title()));
+    for (name, value) in event.facts() {
+        text.push_str(&format!("\n*{}:* {}", name, escape_slack(&value)));
+    }
+    let mut blocks = vec![json!({
+        "type": "section",
+        "text": {"type": "mrkdwn", "text": text},
+    })];
+    if let Some(snippet) = event.snippet() {
+        blocks.push(json!({
+            "type": "section",
+            "text": {
+                "type": "mrkdwn",
+                "text": format!("```{}```", escape_slack(snippet).replace("```", "'''")),
+            },
+        }));
+    }
+    json!({
+        "text": event.title(),
+        "attachments": [{
+            "color": format!("#{:06x}", event.event.color()),
+            "blocks": blocks,
+        }],
+    })
+}
+
+pub fn discord(event: &Event) -> Value {
+    let fields: Vec<Value> = event
+        .facts()
+        .into_iter()
+        .map(|(name, value)| {
+            json!({
+                "name": name,
+                "value": shorten(&value, DISCORD_FIELD),
+                "inline": name != "Command",
+            })
+        })
+        .collect();
+    json!({
+        "embeds": [{
+            "title": shorten(&event.title(), DISCORD_TITLE),
+            "description": event.snippet().map(discord_code),
+            "color": event.event.color(),
+            "fields": fields,
+            "timestamp": format_time(event.time),
+        }],
+    })
+}
+
+/// The content of an `m.room.message` event
+pub fn matrix(event: &Event) -> Value {
+    let mut body = event.title();
+    let mut html = format!("<strong>{}</strong>", escape_html(&event.title()));
+    for (name, value) in event.facts() {
+        body.push_str(&format!("\n{}: {}", name, value));
+        html.push_str(&format!(
+            "<br><strong>{}:</strong> {}",
+            name,
+            escape_html(&value)
+        ));
+    }
+    if let Some(snippet) = event.snippet() {
+        body.push_str(&format!("\n\n{}", snippet));
+        html.push_str(&format!("<pre><code>{}</code></pre>", escape_html(snippet)));
+    }
+    json!({
+        "msgtype": "m.text",
+        "body": body,
+        "format": "org.matrix.custom.html",
+        "formatted_body": html,
+    })
+}
+
+/// A legacy MessageCard, which Teams incoming webhooks and workflows both accept
+pub fn teams(event: &Event) -> Value {
+    let facts: Vec<Value> = event
+        .facts()
+        .into_iter()
+        .map(|(name, value)| json!({"name": name, "value": value}))
+        .collect();
+    json!({
+        "@type": "MessageCard",
+        "@context": "https://schema.org/extensions",
+        "summary": event.title(),
+        "themeColor": format!("{:06x}", event.event.color()),
+        "title": event.title(),
+        "sections": [{
+            "facts": facts,
+            "text": event
+                .snippet()
+                .map(|v| format!("<pre>{}</pre>", escape_html(v))),
+        }],
+    })
+}
+
+/// Subject and plain text body of a mail
+pub fn email(event: &Event) -> (String, String) {
+    let mut body = format!("{}\n\n", event.title());
+    for (name, value) in event.facts() {
+        body.push_str(&format!("{}: {}\n", name, value));
+    }
+    body.push_str(&format!("Time: {}\n", format_time(event.time)));
+    if let Some(v) = event.started {
+        body.push_str(&format!("Started: {}\n", format_time(v)));
+    }
+    if let Some(v) = event.ended {
+        body.push_str(&format!("Ended: {}\n", format_time(v)));
+    }
+    for (name, log) in [("stdout", &event.stdout), ("stderr", &event.stderr)] {
+        if let Some(log) = log.as_deref().filter(|v| !v.is_empty()) {
+            body.push_str(&format!("\n--- end of {} ---\n{}\n", name, log.trim_end()));
+        }
+    }
+    (format!("[crontab_status] {}", event.title()), body)
+}
+
+impl Digest {
+    /// The start of the text, marked if it was cut
+    fn excerpt(&self) -> String {
+        match logs::head(&self.text, DIGEST) {
+            v if v.len() == self.text.len() => v.to_string(),
+            v => format!("{}\n[...]", v),
+        }
+    }
+}
+
+pub fn digest_slack(digest: &Digest) -> Value {
+    json!({
+        "text": digest.title,
+        "attachments": [{
+            "color": format!("#{:06x}", digest.event.color()),
+            "blocks": [{
+                "type": "section",
+                "text": {
+                    "type": "mrkdwn",
+                    "text": format!(
+                        "*{}*\n```{}```",
+                        escape_slack(&digest.title),
+                        escape_slack(&digest.excerpt()).replace("```", "'''")
+                    ),
+                },
+            }],
+        }],
+    })
+}
+
+pub fn digest_discord(digest: &Digest) -> Value {
+    json!({
+        "embeds": [{
+            "title": shorten(&digest.title, DISCORD_TITLE),
+            "description": discord_code(&digest.excerpt()),
+            "color": digest.event.color(),
+            "timestamp": format_time(digest.to),
+        }],
+    })
+}
+
+pub fn digest_matrix(digest: &Digest) -> Value {
+    json!({
+        "msgtype": "m.notice",
+        "body": format!("{}\n\n{}", digest.title, digest.text),
+        "format": "org.matrix.custom.html",
+        "formatted_body": format!(
+            "<strong>{}</strong><pre><code>{}</code></pre>",
+            escape_html(&digest.title),
+            escape_html(&digest.text)
+        ),
+    })
+}
+
+pub fn digest_teams(digest: &Digest) -> Value {
+    json!({
+        "@type": "MessageCard",
+        "@context": "https://schema.org/extensions",
+        "summary": digest.title,
+        "themeColor": format!("{:06x}", digest.event.color()),
+        "title": digest.title,
+        "sections": [{"text": format!("<pre>{}</pre>", escape_html(&digest.text))}],
+    })
+}
+
+pub fn digest_email(digest: &Digest) -> (String, String) {
+    (
+        format!("[crontab_status] {}", digest.title),
+        format!("{}\n\n{}", digest.title, digest.text),
+    )
+}
+
+/// A code block that fits into the description of a Discord embed
+fn discord_code(text: &str) -> String {
+    let text = text.replace("```", "'''");
+    // the fences take 8 characters
+    format!("```\n{}\n```", shorten(&text, DISCORD_DESCRIPTION - 8))
+}
+
+/// The first `chars` characters of `text`, ending in an ellipsis if it was cut
+fn shorten(text: &str, chars: usize) -> String {
+    match text.char_indices().nth(chars) {
+        Some(_) => text.chars().take(chars - 1).chain(['…']).collect(),
+        None => text.to_string(),
+    }
+}
+
+/// Slack reserves these for links and mentions
+fn escape_slack(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortens_by_characters() {
+        assert_eq!(shorten("abc", 3), "abc");
+        assert_eq!(shorten("abcd", 3), "ab…");
+        assert_eq!(shorten("äöüß", 3), "äö…");
+    }
+
+    #[test]
+    fn fits_discord_limits() {
+        let mut event = Event::new(&"j".repeat(300), EventKind::Failed);
+        event.command = Some("ü".repeat(2000));
+        let embed = &discord(&event)["embeds"][0];
+        let length = |v: &Value| v.as_str().unwrap().chars().count();
+        assert_eq!(length(&embed["title"]), DISCORD_TITLE);
+        let command = &embed["fields"][1];
+        assert_eq!(command["name"], "Command");
+        assert_eq!(length(&command["value"]), DISCORD_FIELD);
+        assert!(command["value"].as_str().unwrap().ends_with("ü…"));
+
+        let code = discord_code(&"```".repeat(2000));
+        assert_eq!(code.chars().count(), DISCORD_DESCRIPTION);
+        assert!(code.starts_with("```\n'''") && code.ends_with("…\n```"));
+    }
+}
@@ -0,0 +1,94 @@
+use {
+    crate::notifier::{serialize_optional_time, serialize_time},
+    serde::{Deserialize, Serialize},
+    std::time::{Duration, SystemTime},
+};
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct HistoryRetention {
+    pub max_runs: Option<usize>,
+    pub max_age_days: Option<u64>,
+}
+
+impl Default for HistoryRetention {
+    fn default() -> Self {
+        Self {
+            max_runs: Some(1000),
+            max_age_days: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum RunOutcome {
+    Succeeded,
+    Failed,
+    Missed,
+}
+
+impl RunOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunOutcome::Succeeded => "Succeeded",
+            RunOutcome::Failed => "Failed",
+            RunOutcome::Missed => "Missed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "Succeeded" => Some(RunOutcome::Succeeded),
+            "Failed" => Some(RunOutcome::Failed),
+            "Missed" => Some(RunOutcome::Missed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Run {
+    pub id: i64,
+    pub job_id: String,
+    pub outcome: RunOutcome,
+    #[serde(serialize_with = "serialize_optional_time")]
+    pub started: Option<SystemTime>,
+    /// When the run ended or, for missed runs, when it was expected
+    #[serde(serialize_with = "serialize_time")]
+    pub ended: SystemTime,
+    pub duration: Option<Duration>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub hostname: Option<String>,
+    pub command: Option<String>,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+    pub failure: Option<String>,
+}
+
+impl Run {
+    pub fn new(job_id: &str, outcome: RunOutcome, ended: SystemTime) -> Self {
+        Self {
+            id: 0,
+            job_id: job_id.to_string(),
+            outcome,
+            started: None,
+            ended,
+            duration: None,
+            exit_code: None,
+            signal: None,
+            hostname: None,
+            command: None,
+            stdout: None,
+            stderr: None,
+            failure: None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct HistoryPage {
+    pub runs: Vec<Run>,
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+}
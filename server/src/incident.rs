@@ -0,0 +1,117 @@
+use {
+    crate::{
+        notifier::{serialize_optional_time, serialize_time, EventKind},
+        store::Store,
+    },
+    serde::{Deserialize, Serialize},
+    std::time::SystemTime,
+};
+
+/// A period in which a job had a problem, from the first failed, missed or timed out run until
+/// the job succeeded again
+#[derive(Serialize, Debug, Clone)]
+pub struct Incident {
+    pub id: i64,
+    pub job_id: String,
+    /// The event that opened the incident
+    pub kind: EventKind,
+    #[serde(serialize_with = "serialize_time")]
+    pub opened: SystemTime,
+    /// `None` while the incident is open
+    #[serde(serialize_with = "serialize_optional_time")]
+    pub closed: Option<SystemTime>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineKind {
+    Opened,
+    /// A run of the job ended, `run_id` refers to it
+    Run,
+    /// An event was delivered to a hook, or all attempts failed
+    Notification,
+    Escalated,
+    Acknowledged,
+    Comment,
+    Closed,
+}
+
+impl TimelineKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimelineKind::Opened => "opened",
+            TimelineKind::Run => "run",
+            TimelineKind::Notification => "notification",
+            TimelineKind::Escalated => "escalated",
+            TimelineKind::Acknowledged => "acknowledged",
+            TimelineKind::Comment => "comment",
+            TimelineKind::Closed => "closed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "opened" => Some(TimelineKind::Opened),
+            "run" => Some(TimelineKind::Run),
+            "notification" => Some(TimelineKind::Notification),
+            "escalated" => Some(TimelineKind::Escalated),
+            "acknowledged" => Some(TimelineKind::Acknowledged),
+            "comment" => Some(TimelineKind::Comment),
+            "closed" => Some(TimelineKind::Closed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TimelineEntry {
+    pub id: i64,
+    pub incident_id: i64,
+    #[serde(serialize_with = "serialize_time")]
+    pub time: SystemTime,
+    pub kind: TimelineKind,
+    /// Who acknowledged or commented, if they said so
+    pub author: Option<String>,
+    pub text: String,
+    pub run_id: Option<i64>,
+}
+
+impl TimelineEntry {
+    pub fn new(incident_id: i64, kind: TimelineKind, text: String) -> Self {
+        Self {
+            id: 0,
+            incident_id,
+            time: SystemTime::now(),
+            kind,
+            author: None,
+            text,
+            run_id: None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct IncidentPage {
+    pub incidents: Vec<Incident>,
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct IncidentDetails {
+    #[serde(flatten)]
+    pub incident: Incident,
+    /// Oldest entry first
+    pub timeline: Vec<TimelineEntry>,
+}
+
+/// Adds `entry` to the timeline of its incident, failures are only logged
+pub fn record(store: &Store, entry: &TimelineEntry) {
+    if let Err(e) = store.insert_timeline(entry) {
+        println!(
+            "Unable to add to the timeline of incident {}: {}",
+            entry.incident_id, e
+        )
+    }
+}
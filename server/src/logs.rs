@@ -0,0 +1,131 @@
+use {
+    flate2::{read::GzDecoder, write::GzEncoder, Compression},
+    serde::{Deserialize, Serialize},
+    std::io::{Read, Write},
+};
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LogLimits {
+    /// Largest request body the server accepts, in bytes
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: u64,
+    /// Bytes kept from the start of every output
+    #[serde(default = "default_keep")]
+    pub head: usize,
+    /// Bytes kept from the end of every output
+    #[serde(default = "default_keep")]
+    pub tail: usize,
+}
+
+fn default_max_body_size() -> u64 {
+    16 * 1024 * 1024
+}
+
+fn default_keep() -> usize {
+    256 * 1024
+}
+
+impl Default for LogLimits {
+    fn default() -> Self {
+        Self {
+            max_body_size: default_max_body_size(),
+            head: default_keep(),
+            tail: default_keep(),
+        }
+    }
+}
+
+impl LogLimits {
+    /// Cuts the middle out of logs longer than `head + tail` and marks where it happened
+    pub fn truncate(&self, log: &mut String) {
+        if log.len() <= self.head + self.tail {
+            return;
+        }
+        let head_end = floor_char_boundary(log, self.head);
+        let tail_start = ceil_char_boundary(log, log.len() - self.tail);
+        let marker = format!("\n[... {} bytes truncated ...]\n", tail_start - head_end);
+        log.replace_range(head_end..tail_start, &marker);
+    }
+}
+
+/// The first `bytes` bytes of `log`, without splitting a character
+pub fn head(log: &str, bytes: usize) -> &str {
+    &log[..floor_char_boundary(log, bytes.min(log.len()))]
+}
+
+/// The last `bytes` bytes of `log`, without splitting a character
+pub fn tail(log: &str, bytes: usize) -> &str {
+    if log.len() <= bytes {
+        return log;
+    }
+    &log[ceil_char_boundary(log, log.len() - bytes)..]
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+pub fn compress(text: &str) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    // writing into a Vec can't fail
+    encoder.write_all(text.as_bytes()).unwrap();
+    encoder.finish().unwrap()
+}
+
+pub fn decompress(data: &[u8]) -> std::io::Result<String> {
+    let mut text = String::new();
+    GzDecoder::new(data).read_to_string(&mut text)?;
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(head: usize, tail: usize) -> LogLimits {
+        LogLimits {
+            head,
+            tail,
+            ..LogLimits::default()
+        }
+    }
+
+    #[test]
+    fn keeps_short_logs() {
+        let mut log = "short".to_string();
+        limits(3, 2).truncate(&mut log);
+        assert_eq!(log, "short");
+    }
+
+    #[test]
+    fn cuts_the_middle() {
+        let mut log = "0123456789".to_string();
+        limits(3, 2).truncate(&mut log);
+        assert_eq!(log, "012\n[... 5 bytes truncated ...]\n89");
+    }
+
+    #[test]
+    fn keeps_characters_whole() {
+        // every ä is 2 bytes, the limits end in the middle of one
+        let mut log = "äääääääää".to_string();
+        limits(3, 3).truncate(&mut log);
+        assert_eq!(log, "ä\n[... 14 bytes truncated ...]\nä");
+    }
+
+    #[test]
+    fn head_and_tail() {
+        assert_eq!(head("äöü", 3), "ä");
+        assert_eq!(tail("äöü", 3), "ü");
+        assert_eq!(tail("äöü", 10), "äöü");
+    }
+}
@@ -1,19 +1,127 @@
-#![feature(let_chains)]
-
+mod assertion;
 mod config;
 mod cron;
+mod digest;
+#[cfg(feature = "embed-frontend")]
+mod embedded;
 mod error;
+mod export;
+mod formatter;
+mod history;
+mod incident;
+mod logs;
+mod metrics;
+mod notifier;
+mod route;
+mod search;
+mod stats;
+mod store;
+mod template;
 
 use {
-    config::Config,
-    cron::{ClientUpdate, JobManager, JobStatus},
-    rocket::{fs::FileServer, http::Status, post, routes, serde::json::Json, State},
+    clap::{Parser, Subcommand},
+    config::{Config, SharedConfig},
+    cron::{ClientUpdate, JobManager, JobStatus, Reconciled},
+    export::{ExportCursor, ExportQuery},
+    history::HistoryPage,
+    incident::{IncidentDetails, IncidentPage, TimelineEntry, TimelineKind},
+    metrics::MetricsAuth,
+    notifier::{Delivery, Notifier},
+    rocket::{
+        config::LogLevel,
+        data::{ByteUnit, Limits},
+        fs::FileServer,
+        get,
+        http::{ContentType, Status},
+        post,
+        response::stream::TextStream,
+        routes,
+        serde::json::Json,
+        Route, State,
+    },
+    search::{SearchRequest, SearchResult},
     serde::Deserialize,
+    stats::JobStats,
+    std::{net::IpAddr, path::PathBuf, sync::Arc},
+    store::Store,
 };
 
+#[derive(Parser)]
+#[command(
+    author = "Proxtx",
+    about = "Server of Proxtx/crontab_status. Serves the dashboard and receives the reports of the clients."
+)]
+struct Cli {
+    /// Path of the config file
+    #[arg(
+        long,
+        env = "CRONTAB_STATUS_CONFIG",
+        default_value = "config.toml",
+        global = true
+    )]
+    config: PathBuf,
+    /// Directory of the built dashboard. Defaults to ../frontend/dist/, or the dashboard built
+    /// into the binary with the embed-frontend feature
+    #[arg(long, env = "CRONTAB_STATUS_ASSETS")]
+    assets: Option<PathBuf>,
+    /// Address the server listens on, the port is set in the config. Defaults to 127.0.0.1
+    #[arg(long, env = "CRONTAB_STATUS_ADDRESS")]
+    address: Option<IpAddr>,
+    /// off, critical, normal or debug
+    #[arg(long, env = "CRONTAB_STATUS_LOG_LEVEL", value_parser = |v: &str| v.parse::<LogLevel>())]
+    log_level: Option<LogLevel>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Writes the run history to stdout as CSV or NDJSON
+    Export(ExportQuery),
+}
+
 #[rocket::launch]
 async fn rocket() -> _ {
-    let config = match Config::load().await {
+    let cli = Cli::parse();
+
+    let config = match Config::load(&cli.config).await {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let store = match Store::open(&config.database) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(Command::Export(query)) = cli.command {
+        let jobs = config.jobs.keys().cloned().collect();
+        let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+        if let Err(e) = export::write(&store, &query, jobs, &mut out) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
+    let assets = match cli.assets {
+        Some(v) => Some(v),
+        None if cfg!(feature = "embed-frontend") => None,
+        None => Some(PathBuf::from("../frontend/dist/")),
+    };
+    if let Some(dir) = assets.as_ref().filter(|v| !v.is_dir()) {
+        eprintln!("Error: the asset directory {} doesn't exist", dir.display());
+        std::process::exit(1);
+    }
+
+    let store = Arc::new(store);
+    let notifier = match Notifier::new(&config, store.clone()) {
         Ok(v) => v,
         Err(e) => {
             eprintln!("Error: {}", e);
@@ -21,14 +129,61 @@ async fn rocket() -> _ {
         }
     };
 
-    let manager = cron::JobManager::new(config.clone().jobs);
+    let manager = cron::JobManager::new(
+        config.clone().jobs,
+        config.logs.clone(),
+        store,
+        notifier,
+        config.digest.clone(),
+    );
 
-    let figment = rocket::Config::figment().merge(("port", config.port));
+    let shared_config = SharedConfig::new(config.clone(), cli.config);
+    shared_config.watch(manager.clone());
+
+    let limits = Limits::default().limit("json", ByteUnit::from(config.logs.max_body_size));
+    let mut figment = rocket::Config::figment()
+        .merge(("port", config.port))
+        .merge(("limits", limits));
+    if let Some(address) = cli.address {
+        figment = figment.merge(("address", address));
+    }
+    if let Some(log_level) = cli.log_level {
+        figment = figment.merge(("log_level", log_level));
+    }
     rocket::custom(figment)
         .manage(manager)
-        .manage(config)
-        .mount("/", routes![job_update, get_job, get_jobs])
-        .mount("/", FileServer::from("../frontend/dist/"))
+        .manage(shared_config)
+        .mount(
+            "/",
+            routes![
+                job_update,
+                get_job,
+                get_jobs,
+                get_job_history,
+                get_job_stats,
+                get_metrics,
+                export_history,
+                search_logs,
+                get_deliveries,
+                acknowledge,
+                get_incidents,
+                get_incident,
+                comment_incident,
+                reload_config
+            ],
+        )
+        .mount("/", dashboard(assets))
+}
+
+/// Serves the files in `assets`, or the dashboard built into the binary if there is none
+fn dashboard(assets: Option<PathBuf>) -> Vec<Route> {
+    match assets {
+        Some(dir) => FileServer::from(dir).into(),
+        #[cfg(feature = "embed-frontend")]
+        None => routes![embedded::asset],
+        #[cfg(not(feature = "embed-frontend"))]
+        None => unreachable!("the asset directory has a default without the embedded dashboard"),
+    }
 }
 
 #[derive(Deserialize)]
@@ -39,12 +194,12 @@ struct GuardedRequest<T> {
 
 #[post("/job-update", data = "<update>")]
 async fn job_update(
-    config: &State<Config>,
+    config: &State<SharedConfig>,
     manager: &State<JobManager>,
     update: Json<GuardedRequest<ClientUpdate>>,
 ) -> Status {
     let guard = update.into_inner();
-    if guard.password != config.password {
+    if guard.password != config.get().password {
         return Status::Unauthorized;
     }
     match manager.update(guard.data).await {
@@ -53,26 +208,302 @@ async fn job_update(
     }
 }
 
+/// Loads config.toml again, answers with the error if it is invalid
+#[post("/reload-config", data = "<guard>")]
+async fn reload_config(
+    config: &State<SharedConfig>,
+    manager: &State<JobManager>,
+    guard: Json<GuardedRequest<()>>,
+) -> Result<Json<Reconciled>, (Status, String)> {
+    if guard.password != config.get().password {
+        return Err((Status::Unauthorized, String::new()));
+    }
+    match config.reload(manager).await {
+        Ok(v) => Ok(Json(v)),
+        Err(e) => Err((Status::UnprocessableEntity, e.to_string())),
+    }
+}
+
 #[post("/get-jobs", data = "<guard>")]
 async fn get_jobs(
-    config: &State<Config>,
+    config: &State<SharedConfig>,
     manager: &State<JobManager>,
     guard: Json<GuardedRequest<()>>,
 ) -> Result<Json<Vec<String>>, Status> {
-    if guard.password != config.password {
+    if guard.password != config.get().password {
         return Err(Status::Unauthorized);
     }
-    Ok(Json(manager.get_jobs().into_iter().cloned().collect()))
+    Ok(Json(manager.get_jobs().await))
 }
 
 #[post("/get-job", data = "<guard>")]
 async fn get_job(
-    config: &State<Config>,
+    config: &State<SharedConfig>,
     manager: &State<JobManager>,
     guard: Json<GuardedRequest<String>>,
 ) -> Result<Json<Option<JobStatus>>, Status> {
-    if guard.password != config.password {
+    if guard.password != config.get().password {
         return Err(Status::Unauthorized);
     }
     Ok(Json(manager.get_job(&guard.data).await))
 }
+
+#[derive(Deserialize)]
+struct AcknowledgeRequest {
+    job_id: String,
+    /// Who acknowledged the problem
+    by: Option<String>,
+}
+
+/// Stops the escalation of the open problem of a job
+#[post("/acknowledge", data = "<guard>")]
+async fn acknowledge(
+    config: &State<SharedConfig>,
+    manager: &State<JobManager>,
+    guard: Json<GuardedRequest<AcknowledgeRequest>>,
+) -> Status {
+    let guard = guard.into_inner();
+    if guard.password != config.get().password {
+        return Status::Unauthorized;
+    }
+    match manager.acknowledge(&guard.data.job_id, guard.data.by).await {
+        None => Status::NotFound,
+        Some(false) => Status::Conflict,
+        Some(true) => Status::Ok,
+    }
+}
+
+#[derive(Deserialize)]
+struct HistoryRequest {
+    job_id: String,
+    #[serde(default)]
+    page: usize,
+    per_page: Option<usize>,
+}
+
+#[post("/get-job-history", data = "<guard>")]
+async fn get_job_history(
+    config: &State<SharedConfig>,
+    manager: &State<JobManager>,
+    guard: Json<GuardedRequest<HistoryRequest>>,
+) -> Result<Json<HistoryPage>, Status> {
+    if guard.password != config.get().password {
+        return Err(Status::Unauthorized);
+    }
+    let per_page = guard.data.per_page.unwrap_or(20).clamp(1, 100);
+    match manager
+        .get_history(&guard.data.job_id, guard.data.page, per_page)
+        .await
+    {
+        None => Err(Status::NotFound),
+        Some(Ok(v)) => Ok(Json(v)),
+        Some(Err(e)) => {
+            println!("Unable to load history: {}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[post("/get-job-stats", data = "<guard>")]
+async fn get_job_stats(
+    config: &State<SharedConfig>,
+    manager: &State<JobManager>,
+    guard: Json<GuardedRequest<String>>,
+) -> Result<Json<Vec<JobStats>>, Status> {
+    if guard.password != config.get().password {
+        return Err(Status::Unauthorized);
+    }
+    match manager.get_stats(&guard.data).await {
+        None => Err(Status::NotFound),
+        Some(Ok(v)) => Ok(Json(v)),
+        Some(Err(e)) => {
+            println!("Unable to load stats: {}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[post("/export-history", data = "<guard>")]
+async fn export_history<'a>(
+    config: &State<SharedConfig>,
+    manager: &'a State<JobManager>,
+    guard: Json<GuardedRequest<ExportQuery>>,
+) -> Result<(ContentType, TextStream![String + 'a]), Status> {
+    if guard.password != config.get().password {
+        return Err(Status::Unauthorized);
+    }
+    let query = guard.into_inner().data;
+    let jobs = manager.get_jobs().await;
+    let mut cursor = ExportCursor::new(&query, jobs);
+    let stream = TextStream! {
+        if let Some(v) = query.format.header() {
+            yield v;
+        }
+        loop {
+            match cursor.next_batch(manager.store()) {
+                Ok(Some(runs)) => {
+                    for run in runs.iter() {
+                        yield query.format.render(run);
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    println!("Unable to export history: {}", e);
+                    break;
+                }
+            }
+        }
+    };
+    Ok((query.format.content_type(), stream))
+}
+
+#[post("/search-logs", data = "<guard>")]
+async fn search_logs(
+    config: &State<SharedConfig>,
+    manager: &State<JobManager>,
+    guard: Json<GuardedRequest<SearchRequest>>,
+) -> Result<Json<Vec<SearchResult>>, Status> {
+    if guard.password != config.get().password {
+        return Err(Status::Unauthorized);
+    }
+    let request = &guard.data;
+    let per_page = request.per_page.unwrap_or(20).clamp(1, 100);
+    match manager.store().search_runs(
+        &request.query,
+        request.job_id.as_deref(),
+        request.page * per_page,
+        per_page,
+    ) {
+        Ok(runs) => Ok(Json(
+            runs.into_iter()
+                .map(|v| SearchResult::new(v, &request.query))
+                .collect(),
+        )),
+        Err(e) => {
+            println!("Unable to search logs: {}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct DeliveryRequest {
+    job_id: Option<String>,
+    /// Only attempts that failed
+    #[serde(default)]
+    failed: bool,
+    #[serde(default)]
+    page: usize,
+    per_page: Option<usize>,
+}
+
+#[post("/get-deliveries", data = "<guard>")]
+async fn get_deliveries(
+    config: &State<SharedConfig>,
+    manager: &State<JobManager>,
+    guard: Json<GuardedRequest<DeliveryRequest>>,
+) -> Result<Json<Vec<Delivery>>, Status> {
+    if guard.password != config.get().password {
+        return Err(Status::Unauthorized);
+    }
+    let request = &guard.data;
+    let per_page = request.per_page.unwrap_or(20).clamp(1, 100);
+    match manager.store().deliveries(
+        request.job_id.as_deref(),
+        request.failed,
+        request.page * per_page,
+        per_page,
+    ) {
+        Ok(v) => Ok(Json(v)),
+        Err(e) => {
+            println!("Unable to load deliveries: {}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[get("/metrics")]
+async fn get_metrics(_auth: MetricsAuth, manager: &State<JobManager>) -> String {
+    metrics::render(&manager.get_metrics().await)
+}
+
+#[derive(Deserialize)]
+struct IncidentsRequest {
+    job_id: String,
+    #[serde(default)]
+    page: usize,
+    per_page: Option<usize>,
+}
+
+#[post("/get-incidents", data = "<guard>")]
+async fn get_incidents(
+    config: &State<SharedConfig>,
+    manager: &State<JobManager>,
+    guard: Json<GuardedRequest<IncidentsRequest>>,
+) -> Result<Json<IncidentPage>, Status> {
+    if guard.password != config.get().password {
+        return Err(Status::Unauthorized);
+    }
+    let per_page = guard.data.per_page.unwrap_or(20).clamp(1, 100);
+    match manager
+        .get_incidents(&guard.data.job_id, guard.data.page, per_page)
+        .await
+    {
+        None => Err(Status::NotFound),
+        Some(Ok(v)) => Ok(Json(v)),
+        Some(Err(e)) => {
+            println!("Unable to load incidents: {}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// An incident with its timeline
+#[post("/get-incident", data = "<guard>")]
+async fn get_incident(
+    config: &State<SharedConfig>,
+    manager: &State<JobManager>,
+    guard: Json<GuardedRequest<i64>>,
+) -> Result<Json<IncidentDetails>, Status> {
+    if guard.password != config.get().password {
+        return Err(Status::Unauthorized);
+    }
+    match manager.get_incident(guard.data) {
+        Ok(Some(v)) => Ok(Json(v)),
+        Ok(None) => Err(Status::NotFound),
+        Err(e) => {
+            println!("Unable to load incident: {}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CommentRequest {
+    incident_id: i64,
+    author: Option<String>,
+    text: String,
+}
+
+#[post("/comment-incident", data = "<guard>")]
+async fn comment_incident(
+    config: &State<SharedConfig>,
+    manager: &State<JobManager>,
+    guard: Json<GuardedRequest<CommentRequest>>,
+) -> Status {
+    let guard = guard.into_inner();
+    if guard.password != config.get().password {
+        return Status::Unauthorized;
+    }
+    let request = guard.data;
+    let mut entry = TimelineEntry::new(request.incident_id, TimelineKind::Comment, request.text);
+    entry.author = request.author;
+    match manager.comment_incident(&entry) {
+        Ok(true) => Status::Ok,
+        Ok(false) => Status::NotFound,
+        Err(e) => {
+            println!("Unable to add comment: {}", e);
+            Status::InternalServerError
+        }
+    }
+}
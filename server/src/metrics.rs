@@ -0,0 +1,197 @@
+use {
+    crate::{config::SharedConfig, cron::Status, store::to_unix_millis},
+    rocket::{
+        http::Status as HttpStatus,
+        request::{FromRequest, Outcome, Request},
+    },
+    std::{
+        fmt::Write,
+        sync::atomic::{AtomicU64, Ordering},
+        time::{Duration, SystemTime},
+    },
+};
+
+pub static HOOK_CALLS: AtomicU64 = AtomicU64::new(0);
+pub static HOOK_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+pub struct JobMetrics {
+    pub job_id: String,
+    pub status: &'static str,
+    pub last_success: Option<SystemTime>,
+    pub last_duration: Option<Duration>,
+    pub next_run: Option<SystemTime>,
+    pub runs: u64,
+    pub failures: u64,
+    pub missed: u64,
+}
+
+/// Renders the Prometheus text exposition format
+pub fn render(jobs: &[JobMetrics]) -> String {
+    let mut out = String::new();
+
+    header(
+        &mut out,
+        "job_status",
+        "gauge",
+        "1 for the current status of the job",
+    );
+    for job in jobs {
+        for status in Status::NAMES {
+            let value = (job.status == status) as u8;
+            let _ = writeln!(
+                out,
+                "crontab_status_job_status{{job=\"{}\",status=\"{}\"}} {}",
+                escape(&job.job_id),
+                status,
+                value
+            );
+        }
+    }
+
+    header(
+        &mut out,
+        "job_last_success_timestamp_seconds",
+        "gauge",
+        "When the job last finished successfully",
+    );
+    for job in jobs {
+        if let Some(v) = job.last_success {
+            sample(
+                &mut out,
+                "job_last_success_timestamp_seconds",
+                job,
+                seconds(v),
+            );
+        }
+    }
+
+    header(
+        &mut out,
+        "job_last_run_duration_seconds",
+        "gauge",
+        "How long the last run of the job took",
+    );
+    for job in jobs {
+        if let Some(v) = job.last_duration {
+            sample(
+                &mut out,
+                "job_last_run_duration_seconds",
+                job,
+                v.as_secs_f64(),
+            );
+        }
+    }
+
+    header(
+        &mut out,
+        "job_next_run_timestamp_seconds",
+        "gauge",
+        "When the job is expected to run next",
+    );
+    for job in jobs {
+        if let Some(v) = job.next_run {
+            sample(&mut out, "job_next_run_timestamp_seconds", job, seconds(v));
+        }
+    }
+
+    header(
+        &mut out,
+        "job_runs_total",
+        "counter",
+        "Runs the client reported",
+    );
+    for job in jobs {
+        sample(&mut out, "job_runs_total", job, job.runs as f64);
+    }
+
+    header(
+        &mut out,
+        "job_failures_total",
+        "counter",
+        "Runs that failed",
+    );
+    for job in jobs {
+        sample(&mut out, "job_failures_total", job, job.failures as f64);
+    }
+
+    header(
+        &mut out,
+        "job_missed_total",
+        "counter",
+        "Runs that were never reported",
+    );
+    for job in jobs {
+        sample(&mut out, "job_missed_total", job, job.missed as f64);
+    }
+
+    header(
+        &mut out,
+        "hook_calls_total",
+        "counter",
+        "Hooks that were called",
+    );
+    let _ = writeln!(
+        out,
+        "crontab_status_hook_calls_total {}",
+        HOOK_CALLS.load(Ordering::Relaxed)
+    );
+    header(
+        &mut out,
+        "hook_failures_total",
+        "counter",
+        "Hook calls that failed",
+    );
+    let _ = writeln!(
+        out,
+        "crontab_status_hook_failures_total {}",
+        HOOK_FAILURES.load(Ordering::Relaxed)
+    );
+
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP crontab_status_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE crontab_status_{} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, job: &JobMetrics, value: f64) {
+    let _ = writeln!(
+        out,
+        "crontab_status_{}{{job=\"{}\"}} {}",
+        name,
+        escape(&job.job_id),
+        value
+    );
+}
+
+fn seconds(time: SystemTime) -> f64 {
+    to_unix_millis(time) as f64 / 1000.0
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Prometheus authenticates with `Authorization: Bearer <password>`
+pub struct MetricsAuth;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MetricsAuth {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let config = match request.rocket().state::<SharedConfig>() {
+            Some(v) => v,
+            None => return Outcome::Error((HttpStatus::InternalServerError, ())),
+        };
+        let expected = format!("Bearer {}", config.get().password);
+        match request.headers().get_one("Authorization") {
+            Some(v) if v == expected => Outcome::Success(MetricsAuth),
+            _ => Outcome::Error((HttpStatus::Unauthorized, ())),
+        }
+    }
+}
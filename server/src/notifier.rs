@@ -0,0 +1,952 @@
+use {
+    crate::{
+        config::Config,
+        digest::Digest,
+        error::ConfigResult,
+        export::format_time,
+        formatter,
+        incident::{self, TimelineEntry, TimelineKind},
+        logs, metrics,
+        route::{self, Escalation, Route, Severity},
+        store::Store,
+        template::{self, Template},
+    },
+    hmac::{Hmac, Mac},
+    lettre::{
+        message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+        AsyncTransport, Message, Tokio1Executor,
+    },
+    reqwest::{
+        header::{HeaderValue, CONTENT_TYPE},
+        RequestBuilder,
+    },
+    serde::{Deserialize, Serialize, Serializer},
+    serde_json::Value,
+    sha2::{Digest as _, Sha256},
+    std::{
+        collections::HashMap,
+        error::Error,
+        process::Stdio,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tokio::{
+        io::AsyncWriteExt,
+        process::Command,
+        time::{sleep, timeout},
+    },
+    url::Url,
+};
+
+/// Bytes from the end of stdout and stderr that are included in an event
+pub const EXCERPT: usize = 4096;
+
+/// Carries the signature of hooks with a `secret`
+const SIGNATURE_HEADER: &str = "X-Crontab-Status-Signature";
+
+/// How long delivery attempts are kept in the delivery log
+pub const DELIVERY_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Started,
+    Succeeded,
+    Missed,
+    Failed,
+    TimedOut,
+    Recovered,
+    /// The summary of `[digest]`, never sent for a single job
+    Digest,
+}
+
+impl EventKind {
+    /// Hooks without `events` are called for these
+    const DEFAULT: [EventKind; 4] = [
+        EventKind::Missed,
+        EventKind::Failed,
+        EventKind::TimedOut,
+        EventKind::Recovered,
+    ];
+
+    /// Alarms report a problem, the other events are informational
+    pub fn is_alarm(&self) -> bool {
+        matches!(
+            self,
+            EventKind::Missed | EventKind::Failed | EventKind::TimedOut
+        )
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Started => "started",
+            EventKind::Succeeded => "succeeded",
+            EventKind::Missed => "missed",
+            EventKind::Failed => "failed",
+            EventKind::TimedOut => "timed_out",
+            EventKind::Recovered => "recovered",
+            EventKind::Digest => "digest",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "started" => Some(EventKind::Started),
+            "succeeded" => Some(EventKind::Succeeded),
+            "missed" => Some(EventKind::Missed),
+            "failed" => Some(EventKind::Failed),
+            "timed_out" => Some(EventKind::TimedOut),
+            "recovered" => Some(EventKind::Recovered),
+            "digest" => Some(EventKind::Digest),
+            _ => None,
+        }
+    }
+}
+
+/// Something that happened to a job, sent to its hooks
+#[derive(Serialize, Debug, Clone)]
+pub struct Event {
+    pub event: EventKind,
+    /// Repeats the alarm of a problem that is still open
+    pub reminder: bool,
+    pub job_id: String,
+    pub tags: Vec<String>,
+    pub severity: Severity,
+    pub schedule: String,
+    pub hostname: Option<String>,
+    pub command: Option<String>,
+    #[serde(serialize_with = "serialize_time")]
+    pub time: SystemTime,
+    #[serde(serialize_with = "serialize_optional_time")]
+    pub started: Option<SystemTime>,
+    #[serde(serialize_with = "serialize_optional_time")]
+    pub ended: Option<SystemTime>,
+    #[serde(rename = "duration_seconds", serialize_with = "serialize_seconds")]
+    pub duration: Option<Duration>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub failure: Option<String>,
+    /// Step of the job's escalation the event was sent for, starting at 1
+    pub escalation_step: Option<usize>,
+    /// The incident the event belongs to
+    pub incident_id: Option<i64>,
+    /// The last [EXCERPT] bytes of the output
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+}
+
+#[cfg(test)]
+impl Event {
+    /// An event of `job_id` that only knows what happened
+    pub fn new(job_id: &str, event: EventKind) -> Self {
+        Self {
+            event,
+            reminder: false,
+            job_id: job_id.to_string(),
+            tags: Vec::new(),
+            severity: Severity::default(),
+            schedule: "* * * * *".to_string(),
+            hostname: None,
+            command: None,
+            time: UNIX_EPOCH,
+            started: None,
+            ended: None,
+            duration: None,
+            exit_code: None,
+            signal: None,
+            failure: None,
+            escalation_step: None,
+            incident_id: None,
+            stdout: None,
+            stderr: None,
+        }
+    }
+}
+
+pub fn serialize_time<S>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format_time(*time))
+}
+
+pub fn serialize_optional_time<S>(
+    time: &Option<SystemTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match time {
+        Some(v) => serialize_time(v, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn serialize_seconds<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match duration {
+        Some(v) => serializer.serialize_f64(v.as_secs_f64()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// What is delivered to a hook
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Notification {
+    Event(Event),
+    Digest(Digest),
+}
+
+impl Notification {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Notification::Event(v) => v.event,
+            Notification::Digest(v) => v.event,
+        }
+    }
+
+    /// Empty for digests
+    fn job_id(&self) -> &str {
+        match self {
+            Notification::Event(v) => &v.job_id,
+            Notification::Digest(_) => "",
+        }
+    }
+
+    /// What the message is about, for the log
+    fn describe(&self) -> String {
+        match self {
+            Notification::Event(v) => format!("job {}", v.job_id),
+            Notification::Digest(_) => "the digest".to_string(),
+        }
+    }
+}
+
+/// A plain url in the config is a `json` hook
+#[derive(Serialize, Debug, Clone)]
+pub struct Hook {
+    /// Set for the hooks in `[notifiers]`
+    #[serde(skip)]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub target: Target,
+    #[serde(flatten)]
+    pub options: HookOptions,
+}
+
+/// Settings every kind of hook has
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HookOptions {
+    /// Seconds a delivery attempt may take
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Attempts after the first one failed, waiting 1, 2, 4, ... seconds in between
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Only the first alarm of a problem is sent, until the job succeeds again
+    #[serde(default)]
+    pub dedup: bool,
+    /// Seconds after which the hook is reminded of a problem that is still open
+    pub reminder_interval: Option<u64>,
+    /// Signs the requests of web hooks, see [sign]
+    #[serde(skip_serializing)]
+    pub secret: Option<String>,
+    /// Replaces the request body or the text of the mail
+    pub body: Option<Template>,
+    /// Added to the request, can override the content type. Only the names are serialized, the
+    /// values tend to hold credentials
+    #[serde(default, serialize_with = "serialize_header_names")]
+    pub headers: HashMap<String, Template>,
+    /// Replaces the subject of the mail
+    pub subject: Option<Template>,
+    /// Events the hook is called for, problems and recoveries if omitted
+    pub events: Option<Vec<EventKind>>,
+}
+
+fn serialize_header_names<S>(
+    headers: &HashMap<String, Template>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut names: Vec<&String> = headers.keys().collect();
+    names.sort();
+    names.serialize(serializer)
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+impl Default for HookOptions {
+    fn default() -> Self {
+        Self {
+            timeout: default_timeout(),
+            retries: default_retries(),
+            dedup: false,
+            reminder_interval: None,
+            secret: None,
+            body: None,
+            headers: HashMap::new(),
+            subject: None,
+            events: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Target {
+    /// POSTs the event as JSON
+    Json { url: Url },
+    /// Sends a bare GET request without any details, by default only on alarms
+    Get { url: Url },
+    /// Slack incoming webhook
+    Slack { url: Url },
+    /// Discord channel webhook
+    Discord { url: Url },
+    /// Posts to a room as the user the access token belongs to
+    Matrix {
+        homeserver: Url,
+        room: String,
+        #[serde(skip_serializing)]
+        token: String,
+    },
+    /// Microsoft Teams incoming webhook
+    Teams { url: Url },
+    /// Sends a mail to every recipient through the `[smtp]` server
+    Email { to: Vec<Mailbox> },
+    /// Runs a program with the event in its environment and as JSON on stdin
+    Command { command: Vec<String> },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// Defaults to 587 for `starttls`, 465 for `tls` and 25 for `none`
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: Mailbox,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    #[default]
+    Starttls,
+    /// TLS from the start of the connection
+    Tls,
+    /// Plain text, only meant for local mail servers
+    None,
+}
+
+/// One attempt to deliver an event to a hook
+#[derive(Serialize, Debug, Clone)]
+pub struct Delivery {
+    pub id: i64,
+    pub job_id: String,
+    pub event: EventKind,
+    /// Name of the notifier or kind of the hook
+    pub hook: String,
+    /// Starts at 1
+    pub attempt: u32,
+    #[serde(serialize_with = "serialize_time")]
+    pub time: SystemTime,
+    /// HTTP status, SMTP reply code or exit code of a command, if there was one
+    pub status: Option<u16>,
+    /// Why the attempt failed, `None` if it succeeded
+    pub error: Option<String>,
+}
+
+type Transport = AsyncSmtpTransport<Tokio1Executor>;
+
+/// The response code, if any, and whether the attempt succeeded
+type AttemptResult = (Option<u16>, Result<(), Box<dyn Error + Send + Sync>>);
+
+/// Delivers events to hooks, holding what all of them share
+#[derive(Clone)]
+pub struct Notifier {
+    mailer: Option<(Transport, Mailbox)>,
+    notifiers: Arc<HashMap<String, Hook>>,
+    routes: Arc<Vec<Route>>,
+    escalations: Arc<HashMap<String, Escalation>>,
+    store: Arc<Store>,
+}
+
+impl Notifier {
+    pub fn new(config: &Config, store: Arc<Store>) -> ConfigResult<Self> {
+        let mut notifier = Self {
+            mailer: None,
+            notifiers: Arc::new(config.notifiers.clone()),
+            routes: Arc::new(config.routes.clone()),
+            escalations: Arc::new(config.escalations.clone()),
+            store,
+        };
+        let Some(smtp) = &config.smtp else {
+            return Ok(notifier);
+        };
+        let mut builder = match smtp.tls {
+            SmtpTls::Starttls => Transport::starttls_relay(&smtp.host)?,
+            SmtpTls::Tls => Transport::relay(&smtp.host)?,
+            SmtpTls::None => Transport::builder_dangerous(&smtp.host).port(25),
+        };
+        if let Some(port) = smtp.port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (&smtp.username, &smtp.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        notifier.mailer = Some((builder.build(), smtp.from.clone()));
+        Ok(notifier)
+    }
+
+    /// The hooks of the job and the notifiers `event` is routed to that want it, each with its
+    /// [Hook::key]
+    pub fn targets<'a>(&'a self, hooks: &'a [Hook], event: &Event) -> Vec<(String, &'a Hook)> {
+        let routed = route::route(&self.routes, event)
+            .into_iter()
+            .filter_map(|(name, explicit)| Some((name, explicit, self.notifiers.get(name)?)))
+            .filter(|(_, explicit, hook)| hook.wants(event.event, *explicit))
+            .map(|(_, _, hook)| hook);
+        hooks
+            .iter()
+            .filter(|hook| hook.wants(event.event, false))
+            .chain(routed)
+            .map(|hook| (hook.key(), hook))
+            .collect()
+    }
+
+    /// Delivers `event` to `hook` in the background
+    pub fn send(&self, hook: &Hook, event: &Event) {
+        self.spawn(hook, Notification::Event(event.clone()));
+    }
+
+    /// Delivers `event` to the named notifiers in the background, whether they want it or not
+    pub fn send_named<'a>(&self, names: impl IntoIterator<Item = &'a String>, event: &Event) {
+        for hook in self.named(names) {
+            self.spawn(hook, Notification::Event(event.clone()));
+        }
+    }
+
+    /// Delivers `digest` to the named notifiers in the background
+    pub fn send_digest(&self, names: &[String], digest: &Digest) {
+        for hook in self.named(names) {
+            self.spawn(hook, Notification::Digest(digest.clone()));
+        }
+    }
+
+    fn named<'a>(&self, names: impl IntoIterator<Item = &'a String>) -> Vec<&Hook> {
+        // the config is rejected if it names a notifier that doesn't exist
+        names
+            .into_iter()
+            .filter_map(|v| self.notifiers.get(v))
+            .collect()
+    }
+
+    pub fn escalation(&self, name: &str) -> Option<&Escalation> {
+        self.escalations.get(name)
+    }
+
+    fn spawn(&self, hook: &Hook, message: Notification) {
+        metrics::HOOK_CALLS.fetch_add(1, Ordering::Relaxed);
+        let hook = hook.clone();
+        let notifier = self.clone();
+        tokio::spawn(async move { notifier.deliver(&hook, &message).await });
+    }
+
+    /// Tries until an attempt succeeds or the retries are used up, recording every attempt
+    async fn deliver(&self, hook: &Hook, message: &Notification) {
+        // built once, so retries of matrix messages keep their transaction id
+        let request = hook.request(message);
+        // a request that can't be built won't work on the next attempt either
+        let retries = match request {
+            Some(Err(_)) => 0,
+            _ => hook.options.retries,
+        };
+        let limit = Duration::from_secs(hook.options.timeout);
+        let mut last_error = None;
+        for attempt in 1..=retries + 1 {
+            if attempt > 1 {
+                sleep(Duration::from_secs(1 << (attempt - 2).min(8))).await;
+            }
+            let (status, result) = match timeout(limit, self.attempt(hook, message, &request)).await
+            {
+                Ok(v) => v,
+                Err(_) => (
+                    None,
+                    Err(format!("timed out after {} seconds", hook.options.timeout).into()),
+                ),
+            };
+            let delivery = Delivery {
+                id: 0,
+                job_id: message.job_id().to_string(),
+                event: message.kind(),
+                hook: hook.label().to_string(),
+                attempt,
+                time: SystemTime::now(),
+                status,
+                error: result.err().map(|e| e.to_string()),
+            };
+            if let Err(e) = self.store.insert_delivery(&delivery) {
+                println!("Unable to record delivery of {}: {}", message.describe(), e)
+            }
+            let Some(error) = delivery.error else {
+                self.add_to_incident(hook, message, attempt, None);
+                return;
+            };
+            println!(
+                "Error calling {} hook of {} (attempt {}): {}",
+                delivery.hook,
+                message.describe(),
+                attempt,
+                error
+            );
+            last_error = Some(error);
+        }
+        metrics::HOOK_FAILURES.fetch_add(1, Ordering::Relaxed);
+        self.add_to_incident(hook, message, retries + 1, last_error);
+    }
+
+    /// Adds the outcome of a delivery to the timeline of the incident the event belongs to
+    fn add_to_incident(
+        &self,
+        hook: &Hook,
+        message: &Notification,
+        attempts: u32,
+        error: Option<String>,
+    ) {
+        let Notification::Event(event) = message else {
+            return;
+        };
+        let Some(incident_id) = event.incident_id else {
+            return;
+        };
+        let mut sent = event.event.as_str().to_string();
+        if event.reminder {
+            sent.push_str(" reminder");
+        }
+        if let Some(step) = event.escalation_step {
+            sent.push_str(&format!(" (escalation step {})", step));
+        }
+        let text = match error {
+            None => format!("Sent {} to {}", sent, hook.label()),
+            Some(e) => format!(
+                "Unable to send {} to {} after {} attempts: {}",
+                sent,
+                hook.label(),
+                attempts,
+                e
+            ),
+        };
+        incident::record(
+            &self.store,
+            &TimelineEntry::new(incident_id, TimelineKind::Notification, text),
+        );
+    }
+
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    async fn attempt(
+        &self,
+        hook: &Hook,
+        message: &Notification,
+        request: &Option<Result<(RequestBuilder, String), String>>,
+    ) -> AttemptResult {
+        match (request, &hook.target) {
+            (Some(Err(e)), _) => (None, Err(e.clone().into())),
+            (Some(Ok((request, body))), _) => {
+                // requests with a body of bytes can be cloned, unless building them failed
+                let Some(mut request) = request.try_clone() else {
+                    return (None, Err("unable to build the request".into()));
+                };
+                if let Some(secret) = &hook.options.secret {
+                    request = request.header(SIGNATURE_HEADER, sign(secret, body));
+                }
+                send_request(request).await
+            }
+            (None, Target::Email { to }) => self.send_mail(hook, to, message).await,
+            (None, Target::Command { command }) => run_command(hook, command, message).await,
+            (None, _) => (None, Err("the hook has nothing to send".into())),
+        }
+    }
+
+    async fn send_mail(
+        &self,
+        hook: &Hook,
+        to: &[Mailbox],
+        message: &Notification,
+    ) -> AttemptResult {
+        // the config is rejected if it has email hooks but no `[smtp]` section
+        let Some((transport, from)) = &self.mailer else {
+            return (None, Err("smtp is not configured".into()));
+        };
+        let (mut subject, mut body) = match message {
+            Notification::Event(v) => formatter::email(v),
+            Notification::Digest(v) => formatter::digest_email(v),
+        };
+        if let Some(template) = &hook.options.subject {
+            subject = template.render(message);
+        }
+        if let Some(template) = &hook.options.body {
+            body = template.render(message);
+        }
+        let mut message = Message::builder().from(from.clone()).subject(subject);
+        for recipient in to.iter() {
+            message = message.to(recipient.clone());
+        }
+        let message = match message.body(body) {
+            Ok(v) => v,
+            Err(e) => return (None, Err(e.into())),
+        };
+        match transport.send(message).await {
+            Ok(response) => (Some(response.code().into()), Ok(())),
+            Err(e) => (e.status().map(Into::into), Err(e.into())),
+        }
+    }
+}
+
+impl Target {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Target::Json { .. } => "json",
+            Target::Get { .. } => "get",
+            Target::Slack { .. } => "slack",
+            Target::Discord { .. } => "discord",
+            Target::Matrix { .. } => "matrix",
+            Target::Teams { .. } => "teams",
+            Target::Email { .. } => "email",
+            Target::Command { .. } => "command",
+        }
+    }
+}
+
+async fn send_request(request: RequestBuilder) -> AttemptResult {
+    match request.send().await {
+        Ok(response) => (
+            Some(response.status().as_u16()),
+            response.error_for_status().map(|_| ()).map_err(Into::into),
+        ),
+        Err(e) => (e.status().map(|v| v.as_u16()), Err(e.into())),
+    }
+}
+
+/// Prefix of the environment variables a command gets, `CRONTAB_STATUS_JOB_ID` for example
+const ENV_PREFIX: &str = "CRONTAB_STATUS_";
+
+/// Bytes from the end of stderr shown in the error of a failed command
+const COMMAND_STDERR: usize = 500;
+
+/// The command is killed if it runs into the timeout of the hook
+async fn run_command(hook: &Hook, command: &[String], message: &Notification) -> AttemptResult {
+    // the config rejects empty commands
+    let mut process = Command::new(&command[0]);
+    process
+        .args(&command[1..])
+        .envs(template::values(message).map(|(name, value)| {
+            (
+                format!("{}{}", ENV_PREFIX, name.to_ascii_uppercase()),
+                value,
+            )
+        }))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = match process.spawn() {
+        Ok(v) => v,
+        Err(e) => {
+            return (
+                None,
+                Err(format!("unable to run {}: {}", command[0], e).into()),
+            )
+        }
+    };
+    let body = match &hook.options.body {
+        Some(template) => template.render(message),
+        // serializing strings, numbers and times can't fail
+        None => serde_json::to_string(message).unwrap(),
+    };
+    let stdin = child.stdin.take();
+    let write = async move {
+        // the command doesn't have to read its input
+        if let Some(mut stdin) = stdin {
+            let _ = stdin.write_all(body.as_bytes()).await;
+        }
+    };
+    let (_, output) = tokio::join!(write, child.wait_with_output());
+    let output = match output {
+        Ok(v) => v,
+        Err(e) => return (None, Err(e.into())),
+    };
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = logs::tail(stderr.trim(), COMMAND_STDERR);
+    // `None` if the command was killed by a signal
+    let status = output.status.code().map(|v| v as u16);
+    match (output.status.success(), stderr.is_empty()) {
+        (true, _) => (status, Ok(())),
+        (false, true) => (status, Err(format!("{}", output.status).into())),
+        (false, false) => (status, Err(format!("{}: {}", output.status, stderr).into())),
+    }
+}
+
+/// Makes the transaction ids of matrix messages unique within a server run
+static MATRIX_TRANSACTIONS: AtomicU64 = AtomicU64::new(0);
+
+impl Hook {
+    pub fn new(target: Target) -> Self {
+        Self {
+            name: None,
+            target,
+            options: HookOptions::default(),
+        }
+    }
+
+    /// Name of the notifier or kind of the hook
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(self.target.kind())
+    }
+
+    /// Identifies the hook within a job, also after the config was reordered or reloaded.
+    /// Problems remember which hooks they were sent to by it
+    pub fn key(&self) -> String {
+        if let Some(name) = &self.name {
+            return format!("notifiers.{}", name);
+        }
+        // the target holds secrets like webhook urls, and problems are shown on the dashboard
+        let target = serde_json::to_string(&self.target).unwrap_or_default();
+        let hash: String = Sha256::digest(target.as_bytes())[..8]
+            .iter()
+            .map(|v| format!("{:02x}", v))
+            .collect();
+        format!("hooks.{}.{}", self.target.kind(), hash)
+    }
+
+    pub fn is_email(&self) -> bool {
+        matches!(self.target, Target::Email { .. })
+    }
+
+    /// Whether the hook is called for `event`. `routed` events were asked for by a route, they
+    /// only have to pass the `events` the hook sets itself
+    fn wants(&self, event: EventKind, routed: bool) -> bool {
+        match (&self.options.events, &self.target) {
+            (Some(events), _) => events.contains(&event),
+            (None, _) if routed => true,
+            (None, Target::Get { .. }) => event.is_alarm(),
+            (None, _) => EventKind::DEFAULT.contains(&event),
+        }
+    }
+
+    /// The request and JSON body of every web hook, `None` for hooks that don't send requests. An
+    /// error if a header rendered to an invalid value
+    fn request(&self, message: &Notification) -> Option<Result<(RequestBuilder, String), String>> {
+        let client = reqwest::Client::new();
+        // serializing strings, numbers and times can't fail
+        let json = |event: fn(&Event) -> Value, digest: fn(&Digest) -> Value| {
+            let value = match message {
+                Notification::Event(v) => event(v),
+                Notification::Digest(v) => digest(v),
+            };
+            serde_json::to_string(&value).unwrap()
+        };
+        let (request, body) = match &self.target {
+            Target::Json { url } => (
+                client.post(url.clone()),
+                serde_json::to_string(message).unwrap(),
+            ),
+            Target::Get { url } => (client.get(url.clone()), String::new()),
+            Target::Slack { url } => (
+                client.post(url.clone()),
+                json(formatter::slack, formatter::digest_slack),
+            ),
+            Target::Discord { url } => (
+                client.post(url.clone()),
+                json(formatter::discord, formatter::digest_discord),
+            ),
+            Target::Matrix {
+                homeserver,
+                room,
+                token,
+            } => {
+                let transaction = format!(
+                    "crontab_status.{}.{}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis(),
+                    MATRIX_TRANSACTIONS.fetch_add(1, Ordering::Relaxed)
+                );
+                let mut url = homeserver.clone();
+                // the config only accepts homeservers that can be a base
+                url.path_segments_mut().unwrap().pop_if_empty().extend([
+                    "_matrix",
+                    "client",
+                    "v3",
+                    "rooms",
+                    room,
+                    "send",
+                    "m.room.message",
+                    &transaction,
+                ]);
+                (
+                    client.put(url).bearer_auth(token),
+                    json(formatter::matrix, formatter::digest_matrix),
+                )
+            }
+            Target::Teams { url } => (
+                client.post(url.clone()),
+                json(formatter::teams, formatter::digest_teams),
+            ),
+            Target::Email { .. } | Target::Command { .. } => return None,
+        };
+        let body = match &self.options.body {
+            Some(template) => template.render(message),
+            None => body,
+        };
+        let mut request = match body.is_empty() {
+            true => request,
+            false => request.body(body.clone()),
+        };
+        let content_type = self
+            .options
+            .headers
+            .keys()
+            .any(|v| v.eq_ignore_ascii_case(CONTENT_TYPE.as_str()));
+        if !body.is_empty() && !content_type {
+            request = request.header(CONTENT_TYPE, "application/json");
+        }
+        for (name, template) in self.options.headers.iter() {
+            match HeaderValue::from_str(&template.render(message)) {
+                Ok(value) => request = request.header(name, value),
+                Err(e) => return Some(Err(format!("invalid value of header {}: {}", name, e))),
+            }
+        }
+        Some(Ok((request, body)))
+    }
+}
+
+/// `t=<unix seconds>,v1=<hex HMAC-SHA256 of "<unix seconds>.<body>">`
+fn sign(secret: &str, body: &str) -> String {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    sign_at(secret, body, time)
+}
+
+fn sign_at(secret: &str, body: &str, time: u64) -> String {
+    // HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}.{}", time, body).as_bytes());
+    let signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|v| format!("{:02x}", v))
+        .collect();
+    format!("t={},v1={}", time, signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the example of the README, receivers can check their implementation against it
+    #[test]
+    fn signature() {
+        assert_eq!(
+            sign_at("a long random string", r#"{"event":"failed"}"#, 1704078012),
+            "t=1704078012,v1=600fd2611afe87abca9a1c47c61918ff8640da12a3063704acf9090b90e26b91"
+        );
+    }
+
+    #[test]
+    fn routes_override_the_default_events() {
+        let hook = Hook::new(Target::Json {
+            url: Url::parse("http://127.0.0.1/").unwrap(),
+        });
+        assert!(!hook.wants(EventKind::Succeeded, false));
+        assert!(hook.wants(EventKind::Succeeded, true));
+        assert!(hook.wants(EventKind::Failed, false));
+
+        let mut picky = hook.clone();
+        picky.options.events = Some(vec![EventKind::Failed]);
+        assert!(!picky.wants(EventKind::Succeeded, true));
+    }
+
+    #[test]
+    fn keys_identify_the_target() {
+        let slack = |url: &str| {
+            Hook::new(Target::Slack {
+                url: Url::parse(url).unwrap(),
+            })
+        };
+        let key = slack("https://hooks.slack.com/services/T0/B0/secret").key();
+        assert!(key.starts_with("hooks.slack."));
+        assert!(!key.contains("secret"));
+        assert_eq!(
+            slack("https://hooks.slack.com/services/T0/B0/secret").key(),
+            key
+        );
+        assert_ne!(
+            slack("https://hooks.slack.com/services/T0/B1/other").key(),
+            key
+        );
+        // options like the events or retries can change without losing track of the hook
+        let mut hook = slack("https://hooks.slack.com/services/T0/B0/secret");
+        hook.options.retries = 0;
+        assert_eq!(hook.key(), key);
+
+        hook.name = Some("ops".to_string());
+        assert_eq!(hook.key(), "notifiers.ops");
+    }
+
+    #[test]
+    fn header_with_newline() {
+        let mut hook = Hook::new(Target::Json {
+            url: Url::parse("http://127.0.0.1/").unwrap(),
+        });
+        hook.options
+            .headers
+            .insert("X-Log".to_string(), Template::parse("{{log}}").unwrap());
+        let digest = |text: &str| {
+            Notification::Digest(Digest {
+                event: EventKind::Digest,
+                title: String::new(),
+                text: text.to_string(),
+                from: UNIX_EPOCH,
+                to: UNIX_EPOCH,
+                jobs: Vec::new(),
+            })
+        };
+        assert!(matches!(hook.request(&digest("one line")), Some(Ok(_))));
+        assert_eq!(
+            hook.request(&digest("two\nlines")).unwrap().err().unwrap(),
+            "invalid value of header X-Log: failed to parse header value"
+        );
+    }
+
+    #[test]
+    fn signature_of_empty_body() {
+        assert_eq!(
+            sign_at("a long random string", "", 1704078012),
+            "t=1704078012,v1=8089eef0db46b02a63eb9e6e94b0a046e2b53c8cecbee04343f2927557661a4c"
+        );
+    }
+}
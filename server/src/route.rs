@@ -0,0 +1,252 @@
+use {
+    crate::{
+        assertion::OutputPattern,
+        notifier::{Event, EventKind},
+    },
+    serde::{Deserialize, Serialize},
+    std::time::{Duration, SystemTime},
+};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+/// Sends the events it matches to named notifiers. Empty conditions match everything
+#[derive(Deserialize, Debug, Clone)]
+pub struct Route {
+    /// Matches jobs with at least one of these tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Matched against the job id
+    pub job: Option<OutputPattern>,
+    /// Matched against the hostname the job last reported from
+    pub host: Option<OutputPattern>,
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+    pub min_severity: Option<Severity>,
+    pub notifiers: Vec<String>,
+    /// Default routes are only used if no other route matched
+    #[serde(default)]
+    pub default: bool,
+}
+
+impl Route {
+    pub fn matches(&self, event: &Event) -> bool {
+        (self.tags.is_empty() || self.tags.iter().any(|v| event.tags.contains(v)))
+            && self
+                .job
+                .as_ref()
+                .is_none_or(|v| v.0.is_match(&event.job_id))
+            && self.host.as_ref().is_none_or(|pattern| {
+                event
+                    .hostname
+                    .as_ref()
+                    .is_some_and(|v| pattern.0.is_match(v))
+            })
+            && (self.events.is_empty() || self.events.contains(&event.event))
+            && self.min_severity.is_none_or(|v| event.severity >= v)
+    }
+}
+
+/// Names of the notifiers the matching routes send `event` to, each with whether a route listed
+/// the event in its `events`. Those notifiers get it even if they don't by default
+pub fn route<'a>(routes: &'a [Route], event: &Event) -> Vec<(&'a String, bool)> {
+    let matching = |default: bool| {
+        routes
+            .iter()
+            .filter(move |v| v.default == default && v.matches(event))
+            .peekable()
+    };
+    let mut matched = matching(false);
+    let matched: Vec<&Route> = match matched.peek() {
+        Some(_) => matched.collect(),
+        None => matching(true).collect(),
+    };
+    let mut names: Vec<(&String, bool)> = matched
+        .iter()
+        .flat_map(|route| {
+            route
+                .notifiers
+                .iter()
+                .map(|v| (v, !route.events.is_empty()))
+        })
+        .collect();
+    // explicit first, so dedup keeps it
+    names.sort_by(|a, b| a.0.cmp(b.0).then(b.1.cmp(&a.1)));
+    names.dedup_by(|a, b| a.0 == b.0);
+    names
+}
+
+/// Notifiers that are told about a problem one step after another, until it is acknowledged
+#[derive(Deserialize, Debug, Clone)]
+pub struct Escalation {
+    pub steps: Vec<EscalationStep>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EscalationStep {
+    /// Seconds after the previous step, or after the problem opened for the first step
+    #[serde(default)]
+    pub delay: u64,
+    pub notifiers: Vec<String>,
+}
+
+impl Escalation {
+    /// When step `index` is due for a problem that opened at `since`
+    pub fn due(&self, index: usize, since: SystemTime) -> Option<SystemTime> {
+        let steps = self.steps.get(..=index)?;
+        Some(since + Duration::from_secs(steps.iter().map(|v| v.delay).sum()))
+    }
+
+    /// The notifiers of the steps before `index`
+    pub fn notifiers(&self, index: usize) -> impl Iterator<Item = &String> {
+        self.steps
+            .iter()
+            .take(index)
+            .flat_map(|v| v.notifiers.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routes(toml: &str) -> Vec<Route> {
+        #[derive(Deserialize)]
+        struct Routes {
+            routes: Vec<Route>,
+        }
+        toml::from_str::<Routes>(toml).unwrap().routes
+    }
+
+    fn names(routes: &[Route], event: &Event) -> Vec<(String, bool)> {
+        route(routes, event)
+            .into_iter()
+            .map(|(name, explicit)| (name.clone(), explicit))
+            .collect()
+    }
+
+    #[test]
+    fn matches_events() {
+        let routes = routes(
+            r#"
+            [[routes]]
+            events = ["succeeded"]
+            notifiers = ["chat"]
+            "#,
+        );
+        assert_eq!(
+            names(&routes, &Event::new("backup", EventKind::Succeeded)),
+            [("chat".to_string(), true)]
+        );
+        assert!(names(&routes, &Event::new("backup", EventKind::Failed)).is_empty());
+    }
+
+    #[test]
+    fn matches_job_patterns_and_tags() {
+        let routes = routes(
+            r#"
+            [[routes]]
+            job = "^db_"
+            notifiers = ["db"]
+
+            [[routes]]
+            tags = ["web"]
+            notifiers = ["web"]
+            "#,
+        );
+        let mut event = Event::new("db_dump", EventKind::Failed);
+        assert_eq!(names(&routes, &event), [("db".to_string(), false)]);
+        event.job_id = "backup_db".to_string();
+        assert!(names(&routes, &event).is_empty());
+        event.tags = vec!["web".to_string()];
+        assert_eq!(names(&routes, &event), [("web".to_string(), false)]);
+    }
+
+    #[test]
+    fn falls_back_to_default_routes() {
+        let routes = routes(
+            r#"
+            [[routes]]
+            job = "^db_"
+            notifiers = ["db"]
+
+            [[routes]]
+            default = true
+            notifiers = ["ops"]
+            "#,
+        );
+        assert_eq!(
+            names(&routes, &Event::new("db_dump", EventKind::Failed)),
+            [("db".to_string(), false)]
+        );
+        assert_eq!(
+            names(&routes, &Event::new("backup", EventKind::Failed)),
+            [("ops".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn keeps_explicit_routes_when_deduplicating() {
+        let routes = routes(
+            r#"
+            [[routes]]
+            notifiers = ["ops"]
+
+            [[routes]]
+            events = ["failed"]
+            notifiers = ["ops"]
+            "#,
+        );
+        assert_eq!(
+            names(&routes, &Event::new("backup", EventKind::Failed)),
+            [("ops".to_string(), true)]
+        );
+    }
+
+    #[test]
+    fn escalation_steps_add_up() {
+        let escalation: Escalation = toml::from_str(
+            r#"
+            [[steps]]
+            notifiers = ["oncall"]
+
+            [[steps]]
+            delay = 600
+            notifiers = ["lead"]
+
+            [[steps]]
+            delay = 1800
+            notifiers = ["cto", "lead"]
+            "#,
+        )
+        .unwrap();
+        let since = std::time::UNIX_EPOCH + Duration::from_secs(1000);
+        let after = |v| Some(since + Duration::from_secs(v));
+        assert_eq!(escalation.due(0, since), after(0));
+        assert_eq!(escalation.due(1, since), after(600));
+        assert_eq!(escalation.due(2, since), after(2400));
+        assert_eq!(escalation.due(3, since), None);
+        assert_eq!(escalation.notifiers(0).count(), 0);
+        assert_eq!(
+            escalation.notifiers(2).collect::<Vec<_>>(),
+            ["oncall", "lead"]
+        );
+        assert_eq!(escalation.notifiers(5).count(), 4);
+    }
+}
@@ -0,0 +1,85 @@
+use {
+    crate::{history::Run, notifier::serialize_time},
+    serde::{Deserialize, Serialize},
+    std::time::SystemTime,
+};
+
+/// Characters of context shown around a match
+const CONTEXT: usize = 80;
+
+#[derive(Deserialize, Debug)]
+pub struct SearchRequest {
+    pub query: String,
+    pub job_id: Option<String>,
+    #[serde(default)]
+    pub page: usize,
+    pub per_page: Option<usize>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SearchResult {
+    pub run_id: i64,
+    pub job_id: String,
+    #[serde(serialize_with = "serialize_time")]
+    pub ended: SystemTime,
+    pub stream: &'static str,
+    pub snippet: Snippet,
+}
+
+/// `matched` is the text that should be highlighted
+#[derive(Serialize, Debug, Default)]
+pub struct Snippet {
+    pub before: String,
+    pub matched: String,
+    pub after: String,
+}
+
+impl SearchResult {
+    pub fn new(run: Run, query: &str) -> Self {
+        let stdout = run.stdout.unwrap_or_default();
+        let stderr = run.stderr.unwrap_or_default();
+        // the index ignores punctuation, so the phrase may only match word by word
+        let needles: Vec<&str> = std::iter::once(query.trim())
+            .chain(query.split_whitespace())
+            .filter(|v| !v.is_empty())
+            .collect();
+        let (stream, snippet) = needles
+            .iter()
+            .find_map(|needle| {
+                snippet(&stdout, needle)
+                    .map(|v| ("stdout", v))
+                    .or_else(|| snippet(&stderr, needle).map(|v| ("stderr", v)))
+            })
+            .unwrap_or(("stdout", Snippet::default()));
+        Self {
+            run_id: run.id,
+            job_id: run.job_id,
+            ended: run.ended,
+            stream,
+            snippet,
+        }
+    }
+}
+
+/// Case insensitive search for `needle` in `log`
+fn snippet(log: &str, needle: &str) -> Option<Snippet> {
+    // ascii lowercase keeps the byte offsets of the original log intact
+    let start = log
+        .to_ascii_lowercase()
+        .find(&needle.to_ascii_lowercase())?;
+    let end = start + needle.len();
+    let before = &log[..start];
+    let after = &log[end..];
+    Some(Snippet {
+        before: before
+            .chars()
+            .rev()
+            .take(CONTEXT)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect(),
+        matched: log[start..end].to_string(),
+        after: after.chars().take(CONTEXT).collect(),
+    })
+}
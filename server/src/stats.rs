@@ -0,0 +1,151 @@
+use {
+    crate::{
+        cron::CronExecutionTime,
+        history::{Run, RunOutcome},
+    },
+    serde::Serialize,
+    std::time::Duration,
+};
+
+pub const WINDOWS: [(&str, Duration); 3] = [
+    ("24h", Duration::from_secs(24 * 60 * 60)),
+    ("7d", Duration::from_secs(7 * 24 * 60 * 60)),
+    ("30d", Duration::from_secs(30 * 24 * 60 * 60)),
+];
+
+/// How long the outcomes of runs are kept for the statistics and digests, independent of the
+/// history retention. Covers the longest window and a monthly digest
+pub const OUTCOME_RETENTION: Duration = Duration::from_secs(35 * 24 * 60 * 60);
+
+#[derive(Serialize, Debug, Clone)]
+pub struct JobStats {
+    pub window: String,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub missed: usize,
+    /// Share of reported runs that succeeded
+    pub success_rate: Option<f64>,
+    /// Share of reported runs that started within the grace period of their schedule
+    pub on_time_rate: Option<f64>,
+    pub mean_duration: Option<Duration>,
+    pub p95_duration: Option<Duration>,
+}
+
+impl JobStats {
+    pub fn new(
+        window: &str,
+        runs: &[Run],
+        execution_time: &CronExecutionTime,
+        grace: Duration,
+    ) -> Self {
+        let count = |outcome| runs.iter().filter(|v| v.outcome == outcome).count();
+        let succeeded = count(RunOutcome::Succeeded);
+        let failed = count(RunOutcome::Failed);
+        let reported = succeeded + failed;
+        let rate = |part: usize| match reported {
+            0 => None,
+            total => Some(part as f64 / total as f64),
+        };
+
+        let on_time_rate = match execution_time {
+            CronExecutionTime::Reboot => None,
+            CronExecutionTime::Timing(..) => rate(
+                runs.iter()
+                    .filter_map(|v| v.started)
+                    .filter(|v| execution_time.scheduled_within((*v).into(), grace))
+                    .count(),
+            ),
+        };
+
+        let mut durations: Vec<Duration> = runs
+            .iter()
+            .filter(|v| v.outcome != RunOutcome::Missed)
+            .filter_map(|v| v.duration)
+            .collect();
+        durations.sort();
+        let mean_duration = match durations.len() {
+            0 => None,
+            len => Some(durations.iter().sum::<Duration>() / len as u32),
+        };
+        let p95_duration = match durations.len() {
+            0 => None,
+            len => Some(durations[((len as f64 * 0.95).ceil() as usize).clamp(1, len) - 1]),
+        };
+
+        Self {
+            window: window.to_string(),
+            succeeded,
+            failed,
+            missed: count(RunOutcome::Missed),
+            success_rate: rate(succeeded),
+            on_time_rate,
+            mean_duration,
+            p95_duration,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        serde::{
+            de::value::{Error, StrDeserializer},
+            Deserialize,
+        },
+        std::time::{SystemTime, UNIX_EPOCH},
+    };
+
+    /// Succeeded runs that took the given seconds
+    fn runs(seconds: impl IntoIterator<Item = u64>) -> Vec<Run> {
+        seconds
+            .into_iter()
+            .map(|v| {
+                let mut run = Run::new("backup", RunOutcome::Succeeded, SystemTime::now());
+                run.duration = Some(Duration::from_secs(v));
+                run
+            })
+            .collect()
+    }
+
+    fn p95(runs: &[Run]) -> Option<Duration> {
+        JobStats::new("24h", runs, &CronExecutionTime::Reboot, Duration::ZERO).p95_duration
+    }
+
+    #[test]
+    fn p95_duration() {
+        assert_eq!(p95(&[]), None);
+        assert_eq!(p95(&runs([7])), Some(Duration::from_secs(7)));
+        assert_eq!(p95(&runs([2, 1])), Some(Duration::from_secs(2)));
+        // the nearest rank, the 19th of 20 runs and the 20th of 21
+        assert_eq!(p95(&runs((1..=20).rev())), Some(Duration::from_secs(19)));
+        assert_eq!(p95(&runs(1..=21)), Some(Duration::from_secs(20)));
+        assert_eq!(p95(&runs(1..=100)), Some(Duration::from_secs(95)));
+    }
+
+    #[test]
+    fn ignores_missed_runs() {
+        let mut runs = runs([10, 20]);
+        let mut missed = Run::new("backup", RunOutcome::Missed, SystemTime::now());
+        missed.duration = Some(Duration::from_secs(1000));
+        runs.push(missed);
+        runs[1].outcome = RunOutcome::Failed;
+        let stats = JobStats::new("24h", &runs, &CronExecutionTime::Reboot, Duration::ZERO);
+        assert_eq!((stats.succeeded, stats.failed, stats.missed), (1, 1, 1));
+        assert_eq!(stats.success_rate, Some(0.5));
+        assert_eq!(stats.mean_duration, Some(Duration::from_secs(15)));
+        assert_eq!(stats.p95_duration, Some(Duration::from_secs(20)));
+        assert_eq!(stats.on_time_rate, None);
+    }
+
+    #[test]
+    fn on_time_rate() {
+        let hourly =
+            CronExecutionTime::deserialize(StrDeserializer::<Error>::new("0 * * * *")).unwrap();
+        let mut runs = runs([1, 1]);
+        runs[0].started = Some(UNIX_EPOCH + Duration::from_secs(3600 + 30));
+        runs[1].started = Some(UNIX_EPOCH + Duration::from_secs(3600 + 300));
+        let stats = JobStats::new("24h", &runs, &hourly, Duration::from_secs(60));
+        assert_eq!(stats.on_time_rate, Some(0.5));
+    }
+}
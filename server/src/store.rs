@@ -0,0 +1,686 @@
+use {
+    crate::{
+        cron::JobState,
+        error::StoreResult,
+        history::{HistoryRetention, Run, RunOutcome},
+        incident::{Incident, TimelineEntry, TimelineKind},
+        logs::{compress, decompress},
+        notifier::{Delivery, EventKind},
+    },
+    rusqlite::{
+        params,
+        types::{Type, ValueRef},
+        Connection, OptionalExtension, Row,
+    },
+    std::{
+        path::Path,
+        sync::Mutex,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// Applied in order, the number of applied migrations is kept in `user_version`
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS job_state (
+        job_id TEXT PRIMARY KEY,
+        state TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS runs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        job_id TEXT NOT NULL,
+        outcome TEXT NOT NULL,
+        started INTEGER,
+        ended INTEGER NOT NULL,
+        exit_code INTEGER,
+        hostname TEXT,
+        command TEXT,
+        output TEXT,
+        failure TEXT
+    );
+    CREATE INDEX IF NOT EXISTS runs_job_ended ON runs (job_id, ended);",
+    "ALTER TABLE runs RENAME COLUMN output TO stdout;
+    ALTER TABLE runs ADD COLUMN stderr TEXT;
+    ALTER TABLE runs ADD COLUMN signal INTEGER;",
+    "CREATE VIRTUAL TABLE IF NOT EXISTS run_search USING fts5(
+        stdout, stderr, content = '', contentless_delete = 1
+    );",
+    "CREATE TABLE IF NOT EXISTS deliveries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        job_id TEXT NOT NULL,
+        event TEXT NOT NULL,
+        hook TEXT NOT NULL,
+        attempt INTEGER NOT NULL,
+        time INTEGER NOT NULL,
+        status INTEGER,
+        error TEXT
+    );
+    CREATE INDEX IF NOT EXISTS deliveries_job_time ON deliveries (job_id, time);",
+    "CREATE TABLE IF NOT EXISTS incidents (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        job_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        opened INTEGER NOT NULL,
+        closed INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS incidents_job_opened ON incidents (job_id, opened);
+    CREATE TABLE IF NOT EXISTS timeline (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        incident_id INTEGER NOT NULL,
+        time INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        author TEXT,
+        text TEXT NOT NULL,
+        run_id INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS timeline_incident ON timeline (incident_id, time);",
+    "CREATE TABLE IF NOT EXISTS run_outcomes (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        job_id TEXT NOT NULL,
+        outcome TEXT NOT NULL,
+        started INTEGER,
+        ended INTEGER NOT NULL,
+        failure TEXT
+    );
+    CREATE INDEX IF NOT EXISTS run_outcomes_job_ended ON run_outcomes (job_id, ended);
+    INSERT INTO run_outcomes (job_id, outcome, started, ended, failure)
+        SELECT job_id, outcome, started, ended, failure FROM runs ORDER BY id;",
+];
+
+/// The migration that added the search index, runs stored before it have to be indexed
+const SEARCH_MIGRATION: usize = 3;
+
+pub struct Store {
+    connection: Mutex<Connection>,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> StoreResult<Self> {
+        let mut connection = Connection::open(path)?;
+        let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            // a migration that fails halfway is rolled back and retried on the next start
+            let transaction = connection.transaction()?;
+            transaction.execute_batch(migration)?;
+            if index + 1 == SEARCH_MIGRATION {
+                index_runs(&transaction)?;
+            }
+            transaction.pragma_update(None, "user_version", index + 1)?;
+            transaction.commit()?;
+        }
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    pub fn load_state(&self, job_id: &str) -> StoreResult<Option<JobState>> {
+        let state: Option<String> = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT state FROM job_state WHERE job_id = ?1",
+                params![job_id],
+                |row| read_log(row, 0),
+            )
+            .optional()?
+            .flatten();
+        match state {
+            Some(v) => Ok(Some(serde_json::from_str(&v)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn save_state(&self, job_id: &str, state: &JobState) -> StoreResult<()> {
+        let state = compress(&serde_json::to_string(state)?);
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO job_state (job_id, state) VALUES (?1, ?2)
+            ON CONFLICT (job_id) DO UPDATE SET state = excluded.state",
+            params![job_id, state],
+        )?;
+        Ok(())
+    }
+
+    /// The last time the server was known to be alive
+    pub fn last_heartbeat(&self) -> StoreResult<Option<SystemTime>> {
+        let heartbeat: Option<i64> = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'heartbeat'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(heartbeat.map(from_unix))
+    }
+
+    pub fn heartbeat(&self) -> StoreResult<()> {
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO meta (key, value) VALUES ('heartbeat', ?1)
+            ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![to_unix(SystemTime::now())],
+        )?;
+        Ok(())
+    }
+
+    pub fn insert_run(&self, run: &Run) -> StoreResult<i64> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        let id = insert_run(&transaction, run)?;
+        transaction.commit()?;
+        Ok(id)
+    }
+
+    /// Writes all runs at once, returns the id of the last one
+    pub fn insert_runs(&self, runs: &[Run]) -> StoreResult<Option<i64>> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        let mut id = None;
+        for run in runs {
+            id = Some(insert_run(&transaction, run)?);
+        }
+        transaction.commit()?;
+        Ok(id)
+    }
+
+    /// Newest runs whose logs contain all words of `phrase` in order
+    pub fn search_runs(
+        &self,
+        phrase: &str,
+        job_id: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> StoreResult<Vec<Run>> {
+        // the index only holds words, a phrase without any can't match and some SQLite versions reject it
+        if !phrase.chars().any(char::is_alphanumeric) {
+            return Ok(Vec::new());
+        }
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT runs.id, job_id, outcome, started, ended, exit_code, signal, hostname, command, runs.stdout, runs.stderr, failure
+            FROM run_search JOIN runs ON runs.id = run_search.rowid
+            WHERE run_search MATCH ?1 AND (?2 IS NULL OR job_id = ?2)
+            ORDER BY ended DESC, runs.id DESC LIMIT ?3 OFFSET ?4",
+        )?;
+        let query = format!("\"{}\"", phrase.replace('"', "\"\""));
+        let runs = statement
+            .query_map(
+                params![query, job_id, limit as i64, offset as i64],
+                run_from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(runs)
+    }
+
+    /// Newest runs first
+    pub fn runs(&self, job_id: &str, offset: usize, limit: usize) -> StoreResult<Vec<Run>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT id, job_id, outcome, started, ended, exit_code, signal, hostname, command, stdout, stderr, failure
+            FROM runs WHERE job_id = ?1 ORDER BY ended DESC, id DESC LIMIT ?2 OFFSET ?3",
+        )?;
+        let runs = statement
+            .query_map(params![job_id, limit as i64, offset as i64], run_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(runs)
+    }
+
+    /// Runs that ended after `since`, without their logs
+    pub fn runs_since(&self, job_id: &str, since: SystemTime) -> StoreResult<Vec<Run>> {
+        let connection = self.connection.lock().unwrap();
+        // the outcomes outlive the history, so statistics don't depend on its retention
+        let mut statement = connection.prepare(
+            "SELECT id, job_id, outcome, started, ended, NULL, NULL, NULL, NULL, NULL, NULL, failure
+            FROM run_outcomes WHERE job_id = ?1 AND ended >= ?2 ORDER BY ended",
+        )?;
+        let runs = statement
+            .query_map(params![job_id, to_unix_millis(since)], run_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(runs)
+    }
+
+    /// Runs ordered by id with an id above `after_id`, `from` is inclusive and `to` exclusive
+    pub fn export_runs(
+        &self,
+        job_id: &str,
+        from: Option<SystemTime>,
+        to: Option<SystemTime>,
+        after_id: i64,
+        limit: usize,
+    ) -> StoreResult<Vec<Run>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT id, job_id, outcome, started, ended, exit_code, signal, hostname, command, stdout, stderr, failure
+            FROM runs WHERE job_id = ?1 AND id > ?2 AND ended >= ?3 AND ended < ?4
+            ORDER BY id LIMIT ?5",
+        )?;
+        let runs = statement
+            .query_map(
+                params![
+                    job_id,
+                    after_id,
+                    from.map(to_unix_millis).unwrap_or(i64::MIN),
+                    to.map(to_unix_millis).unwrap_or(i64::MAX),
+                    limit as i64
+                ],
+                run_from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(runs)
+    }
+
+    pub fn count_runs(&self, job_id: &str) -> StoreResult<usize> {
+        let count: i64 = self.connection.lock().unwrap().query_row(
+            "SELECT COUNT(*) FROM runs WHERE job_id = ?1",
+            params![job_id],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    pub fn prune_runs(&self, job_id: &str, retention: &HistoryRetention) -> StoreResult<()> {
+        let connection = self.connection.lock().unwrap();
+        let mut pruned: Vec<i64> = Vec::new();
+        if let Some(max_runs) = retention.max_runs {
+            let mut statement = connection.prepare(
+                "DELETE FROM runs WHERE job_id = ?1 AND id NOT IN
+                (SELECT id FROM runs WHERE job_id = ?1 ORDER BY ended DESC, id DESC LIMIT ?2)
+                RETURNING id",
+            )?;
+            for id in statement.query_map(params![job_id, max_runs as i64], |row| row.get(0))? {
+                pruned.push(id?);
+            }
+        }
+        if let Some(max_age_days) = retention.max_age_days {
+            let oldest = SystemTime::now() - Duration::from_secs(max_age_days * 24 * 60 * 60);
+            let mut statement = connection
+                .prepare("DELETE FROM runs WHERE job_id = ?1 AND ended < ?2 RETURNING id")?;
+            for id in
+                statement.query_map(params![job_id, to_unix_millis(oldest)], |row| row.get(0))?
+            {
+                pruned.push(id?);
+            }
+        }
+        for id in pruned {
+            connection.execute("DELETE FROM run_search WHERE rowid = ?1", params![id])?;
+        }
+        Ok(())
+    }
+
+    pub fn insert_delivery(&self, delivery: &Delivery) -> StoreResult<i64> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "INSERT INTO deliveries (job_id, event, hook, attempt, time, status, error)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                delivery.job_id,
+                delivery.event.as_str(),
+                delivery.hook,
+                delivery.attempt,
+                to_unix_millis(delivery.time),
+                delivery.status,
+                delivery.error
+            ],
+        )?;
+        Ok(connection.last_insert_rowid())
+    }
+
+    /// Newest attempts first, of every job if `job_id` is `None`
+    pub fn deliveries(
+        &self,
+        job_id: Option<&str>,
+        failed_only: bool,
+        offset: usize,
+        limit: usize,
+    ) -> StoreResult<Vec<Delivery>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT id, job_id, event, hook, attempt, time, status, error FROM deliveries
+            WHERE (?1 IS NULL OR job_id = ?1) AND (NOT ?2 OR error IS NOT NULL)
+            ORDER BY time DESC, id DESC LIMIT ?3 OFFSET ?4",
+        )?;
+        let deliveries = statement
+            .query_map(
+                params![job_id, failed_only, limit as i64, offset as i64],
+                delivery_from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(deliveries)
+    }
+
+    /// Forgets the outcomes of runs that ended before `older_than`, whether or not they are
+    /// still in the history
+    pub fn prune_outcomes(&self, older_than: SystemTime) -> StoreResult<()> {
+        self.connection.lock().unwrap().execute(
+            "DELETE FROM run_outcomes WHERE ended < ?1",
+            params![to_unix_millis(older_than)],
+        )?;
+        Ok(())
+    }
+
+    pub fn prune_deliveries(&self, older_than: SystemTime) -> StoreResult<()> {
+        self.connection.lock().unwrap().execute(
+            "DELETE FROM deliveries WHERE time < ?1",
+            params![to_unix_millis(older_than)],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the id of the new incident
+    pub fn open_incident(
+        &self,
+        job_id: &str,
+        kind: EventKind,
+        opened: SystemTime,
+    ) -> StoreResult<i64> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "INSERT INTO incidents (job_id, kind, opened) VALUES (?1, ?2, ?3)",
+            params![job_id, kind.as_str(), to_unix_millis(opened)],
+        )?;
+        Ok(connection.last_insert_rowid())
+    }
+
+    pub fn close_incident(&self, id: i64, closed: SystemTime) -> StoreResult<()> {
+        self.connection.lock().unwrap().execute(
+            "UPDATE incidents SET closed = ?2 WHERE id = ?1",
+            params![id, to_unix_millis(closed)],
+        )?;
+        Ok(())
+    }
+
+    pub fn incident(&self, id: i64) -> StoreResult<Option<Incident>> {
+        let incident = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT id, job_id, kind, opened, closed FROM incidents WHERE id = ?1",
+                params![id],
+                incident_from_row,
+            )
+            .optional()?;
+        Ok(incident)
+    }
+
+    /// Newest incidents first
+    pub fn incidents(
+        &self,
+        job_id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> StoreResult<Vec<Incident>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT id, job_id, kind, opened, closed FROM incidents WHERE job_id = ?1
+            ORDER BY opened DESC, id DESC LIMIT ?2 OFFSET ?3",
+        )?;
+        let incidents = statement
+            .query_map(
+                params![job_id, limit as i64, offset as i64],
+                incident_from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(incidents)
+    }
+
+    pub fn count_incidents(&self, job_id: &str) -> StoreResult<usize> {
+        let count: i64 = self.connection.lock().unwrap().query_row(
+            "SELECT COUNT(*) FROM incidents WHERE job_id = ?1",
+            params![job_id],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    pub fn insert_timeline(&self, entry: &TimelineEntry) -> StoreResult<i64> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "INSERT INTO timeline (incident_id, time, kind, author, text, run_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                entry.incident_id,
+                to_unix_millis(entry.time),
+                entry.kind.as_str(),
+                entry.author,
+                entry.text,
+                entry.run_id
+            ],
+        )?;
+        Ok(connection.last_insert_rowid())
+    }
+
+    /// Oldest entries first
+    pub fn timeline(&self, incident_id: i64) -> StoreResult<Vec<TimelineEntry>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT id, incident_id, time, kind, author, text, run_id FROM timeline
+            WHERE incident_id = ?1 ORDER BY time, id",
+        )?;
+        let timeline = statement
+            .query_map(params![incident_id], timeline_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(timeline)
+    }
+}
+
+/// Adds the logs of every run to the search index
+fn index_runs(connection: &Connection) -> StoreResult<()> {
+    let mut statement = connection.prepare("SELECT id, stdout, stderr FROM runs")?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        connection.execute(
+            "INSERT INTO run_search (rowid, stdout, stderr) VALUES (?1, ?2, ?3)",
+            params![row.get::<_, i64>(0)?, read_log(row, 1)?, read_log(row, 2)?],
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes a run to the history, the kept outcomes and the search index
+fn insert_run(connection: &Connection, run: &Run) -> StoreResult<i64> {
+    connection.execute(
+        "INSERT INTO runs
+        (job_id, outcome, started, ended, exit_code, signal, hostname, command, stdout, stderr, failure)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            run.job_id,
+            run.outcome.as_str(),
+            run.started.map(to_unix_millis),
+            to_unix_millis(run.ended),
+            run.exit_code,
+            run.signal,
+            run.hostname,
+            run.command,
+            run.stdout.as_deref().map(compress),
+            run.stderr.as_deref().map(compress),
+            run.failure
+        ],
+    )?;
+    let id = connection.last_insert_rowid();
+    connection.execute(
+        "INSERT INTO run_outcomes (job_id, outcome, started, ended, failure)
+        VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            run.job_id,
+            run.outcome.as_str(),
+            run.started.map(to_unix_millis),
+            to_unix_millis(run.ended),
+            run.failure
+        ],
+    )?;
+    connection.execute(
+        "INSERT INTO run_search (rowid, stdout, stderr) VALUES (?1, ?2, ?3)",
+        params![id, run.stdout, run.stderr],
+    )?;
+    Ok(id)
+}
+
+fn incident_from_row(row: &Row) -> rusqlite::Result<Incident> {
+    let kind: String = row.get(2)?;
+    Ok(Incident {
+        id: row.get(0)?,
+        job_id: row.get(1)?,
+        kind: EventKind::parse(&kind).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                2,
+                Type::Text,
+                format!("unknown event {}", kind).into(),
+            )
+        })?,
+        opened: from_unix_millis(row.get(3)?),
+        closed: row.get::<_, Option<i64>>(4)?.map(from_unix_millis),
+    })
+}
+
+fn timeline_from_row(row: &Row) -> rusqlite::Result<TimelineEntry> {
+    let kind: String = row.get(3)?;
+    Ok(TimelineEntry {
+        id: row.get(0)?,
+        incident_id: row.get(1)?,
+        time: from_unix_millis(row.get(2)?),
+        kind: TimelineKind::parse(&kind).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                3,
+                Type::Text,
+                format!("unknown timeline entry {}", kind).into(),
+            )
+        })?,
+        author: row.get(4)?,
+        text: row.get(5)?,
+        run_id: row.get(6)?,
+    })
+}
+
+fn delivery_from_row(row: &Row) -> rusqlite::Result<Delivery> {
+    let event: String = row.get(2)?;
+    Ok(Delivery {
+        id: row.get(0)?,
+        job_id: row.get(1)?,
+        event: EventKind::parse(&event).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                2,
+                Type::Text,
+                format!("unknown event {}", event).into(),
+            )
+        })?,
+        hook: row.get(3)?,
+        attempt: row.get(4)?,
+        time: from_unix_millis(row.get(5)?),
+        status: row.get(6)?,
+        error: row.get(7)?,
+    })
+}
+
+fn run_from_row(row: &Row) -> rusqlite::Result<Run> {
+    let outcome: String = row.get(2)?;
+    let started: Option<SystemTime> = row.get::<_, Option<i64>>(3)?.map(from_unix_millis);
+    let ended = from_unix_millis(row.get(4)?);
+    Ok(Run {
+        id: row.get(0)?,
+        job_id: row.get(1)?,
+        outcome: RunOutcome::parse(&outcome).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                2,
+                Type::Text,
+                format!("unknown run outcome {}", outcome).into(),
+            )
+        })?,
+        started,
+        ended,
+        duration: started.and_then(|v| ended.duration_since(v).ok()),
+        exit_code: row.get(5)?,
+        signal: row.get(6)?,
+        hostname: row.get(7)?,
+        command: row.get(8)?,
+        stdout: read_log(row, 9)?,
+        stderr: read_log(row, 10)?,
+        failure: row.get(11)?,
+    })
+}
+
+/// Reads a compressed log, logs stored before compression was added are plain text
+fn read_log(row: &Row, index: usize) -> rusqlite::Result<Option<String>> {
+    match row.get_ref(index)? {
+        ValueRef::Null => Ok(None),
+        ValueRef::Blob(v) => decompress(v)
+            .map(Some)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, Type::Blob, Box::new(e))),
+        _ => row.get(index),
+    }
+}
+
+pub fn to_unix(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+pub fn from_unix(secs: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64)
+}
+
+pub fn to_unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+pub fn from_unix_millis(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_and_inserts_runs() {
+        let store = Store::open(Path::new(":memory:")).unwrap();
+        let version: usize = store
+            .connection
+            .lock()
+            .unwrap()
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+
+        let ended = SystemTime::now();
+        let mut run = Run::new("backup", RunOutcome::Failed, ended);
+        run.stdout = Some(String::from("disk full"));
+        let id = store.insert_run(&run).unwrap();
+        assert_eq!(store.runs("backup", 0, 10).unwrap()[0].id, id);
+        assert_eq!(store.search_runs("disk", None, 0, 10).unwrap()[0].id, id);
+        assert_eq!(
+            store
+                .runs_since("backup", ended - Duration::from_secs(1))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let missed: Vec<Run> = (1..=3)
+            .map(|v| Run::new("sync", RunOutcome::Missed, ended + Duration::from_secs(v)))
+            .collect();
+        let last = store.insert_runs(&missed).unwrap();
+        assert_eq!(store.runs("sync", 0, 10).unwrap()[0].id, last.unwrap());
+        assert_eq!(store.count_runs("sync").unwrap(), 3);
+        assert_eq!(store.insert_runs(&[]).unwrap(), None);
+    }
+
+    #[test]
+    fn ignores_queries_without_words() {
+        let store = Store::open(Path::new(":memory:")).unwrap();
+        let mut run = Run::new("backup", RunOutcome::Failed, SystemTime::now());
+        run.stdout = Some(String::from("disk full !?"));
+        store.insert_run(&run).unwrap();
+        for query in ["", "  ", "\"", "!?"] {
+            assert!(store.search_runs(query, None, 0, 10).unwrap().is_empty());
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use {
+    crate::{
+        export::format_time,
+        notifier::{Event, Notification},
+    },
+    serde::{Serialize, Serializer},
+};
+
+/// Text with `{{placeholder}}`s that are filled in with the details of an event or digest.
+/// `{{placeholder | json}}` inserts the value as a JSON string, or `null` if it is unknown
+#[derive(Debug, Clone)]
+pub struct Template {
+    source: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Text(String),
+    Placeholder { field: Field, json: bool },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    JobId,
+    Event,
+    Reminder,
+    Title,
+    Tags,
+    Severity,
+    Schedule,
+    Hostname,
+    Command,
+    Time,
+    Started,
+    Ended,
+    Duration,
+    ExitCode,
+    Signal,
+    Failure,
+    EscalationStep,
+    IncidentId,
+    Stdout,
+    Stderr,
+    Log,
+}
+
+impl Field {
+    const ALL: [Field; 21] = [
+        Field::JobId,
+        Field::Event,
+        Field::Reminder,
+        Field::Title,
+        Field::Tags,
+        Field::Severity,
+        Field::Schedule,
+        Field::Hostname,
+        Field::Command,
+        Field::Time,
+        Field::Started,
+        Field::Ended,
+        Field::Duration,
+        Field::ExitCode,
+        Field::Signal,
+        Field::Failure,
+        Field::EscalationStep,
+        Field::IncidentId,
+        Field::Stdout,
+        Field::Stderr,
+        Field::Log,
+    ];
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "status" => Some(Field::Event),
+            "host" => Some(Field::Hostname),
+            name => Field::ALL.into_iter().find(|v| v.name() == name),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Field::JobId => "job_id",
+            Field::Event => "event",
+            Field::Reminder => "reminder",
+            Field::Title => "title",
+            Field::Tags => "tags",
+            Field::Severity => "severity",
+            Field::Schedule => "schedule",
+            Field::Hostname => "hostname",
+            Field::Command => "command",
+            Field::Time => "time",
+            Field::Started => "started",
+            Field::Ended => "ended",
+            Field::Duration => "duration",
+            Field::ExitCode => "exit_code",
+            Field::Signal => "signal",
+            Field::Failure => "failure",
+            Field::EscalationStep => "escalation_step",
+            Field::IncidentId => "incident_id",
+            Field::Stdout => "stdout",
+            Field::Stderr => "stderr",
+            Field::Log => "log",
+        }
+    }
+
+    /// Whether the value can span lines
+    fn multiline(&self) -> bool {
+        matches!(
+            self,
+            Field::Command | Field::Failure | Field::Stdout | Field::Stderr | Field::Log
+        )
+    }
+
+    /// `None` if the message doesn't know the value
+    fn value(&self, message: &Notification) -> Option<String> {
+        let digest = match message {
+            Notification::Event(event) => return self.event_value(event),
+            Notification::Digest(v) => v,
+        };
+        // the period of the digest is given as its start and end, the text as its log
+        match self {
+            Field::Event => Some(digest.event.as_str().to_string()),
+            Field::Title => Some(digest.title.clone()),
+            Field::Time | Field::Ended => Some(format_time(digest.to)),
+            Field::Started => Some(format_time(digest.from)),
+            Field::Log => Some(digest.text.clone()),
+            _ => None,
+        }
+    }
+
+    fn event_value(&self, event: &Event) -> Option<String> {
+        match self {
+            Field::JobId => Some(event.job_id.clone()),
+            Field::Event => Some(event.event.as_str().to_string()),
+            Field::Reminder => Some(event.reminder.to_string()),
+            Field::Title => Some(event.title()),
+            Field::Tags => Some(event.tags.join(",")),
+            Field::Severity => Some(event.severity.as_str().to_string()),
+            Field::Schedule => Some(event.schedule.clone()),
+            Field::Hostname => event.hostname.clone(),
+            Field::Command => event.command.clone(),
+            Field::Time => Some(format_time(event.time)),
+            Field::Started => event.started.map(format_time),
+            Field::Ended => event.ended.map(format_time),
+            Field::Duration => event.duration.map(|v| format!("{:.3}", v.as_secs_f64())),
+            Field::ExitCode => event.exit_code.map(|v| v.to_string()),
+            Field::Signal => event.signal.map(|v| v.to_string()),
+            Field::Failure => event.failure.clone(),
+            Field::EscalationStep => event.escalation_step.map(|v| v.to_string()),
+            Field::IncidentId => event.incident_id.map(|v| v.to_string()),
+            Field::Stdout => event.stdout.clone(),
+            Field::Stderr => event.stderr.clone(),
+            Field::Log => event.snippet().map(String::from),
+        }
+    }
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let after = &rest[start + 2..];
+            let end = after.find("}}").ok_or_else(|| {
+                format!(
+                    "unclosed placeholder at byte {}",
+                    source.len() - rest.len() + start
+                )
+            })?;
+            let (name, filter) = match after[..end].split_once('|') {
+                Some((name, filter)) => (name.trim(), Some(filter.trim())),
+                None => (after[..end].trim(), None),
+            };
+            let field = Field::parse(name)
+                .ok_or_else(|| format!("unknown placeholder {{{{{}}}}}", name))?;
+            let json = match filter {
+                None => false,
+                Some("json") => true,
+                Some(v) => return Err(format!("unknown filter {} of {{{{{}}}}}", v, name)),
+            };
+            parts.push(Part::Placeholder { field, json });
+            rest = &after[end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            parts,
+        })
+    }
+
+    /// The first placeholder that can insert a line break, `{{name | json}}` escapes them
+    pub fn multiline_placeholder(&self) -> Option<&'static str> {
+        self.parts.iter().find_map(|v| match v {
+            Part::Placeholder { field, json: false } if field.multiline() => Some(field.name()),
+            _ => None,
+        })
+    }
+
+    pub fn render(&self, message: &Notification) -> String {
+        let mut rendered = String::new();
+        for part in self.parts.iter() {
+            match part {
+                Part::Text(v) => rendered.push_str(v),
+                Part::Placeholder { field, json: false } => {
+                    rendered.push_str(&field.value(message).unwrap_or_default())
+                }
+                // serializing a string can't fail
+                Part::Placeholder { field, json: true } => {
+                    rendered.push_str(&serde_json::to_string(&field.value(message)).unwrap())
+                }
+            }
+        }
+        rendered
+    }
+}
+
+impl Serialize for Template {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+/// Name and value of every placeholder the message knows
+pub fn values(message: &Notification) -> impl Iterator<Item = (&'static str, String)> + '_ {
+    Field::ALL
+        .into_iter()
+        .filter_map(|field| Some((field.name(), field.value(message)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_placeholders() {
+        let template = Template::parse("{{ job_id }} {{status}}: {{log | json}}").unwrap();
+        assert_eq!(template.parts.len(), 5);
+        assert!(matches!(
+            template.parts[4],
+            Part::Placeholder {
+                field: Field::Log,
+                json: true
+            }
+        ));
+    }
+
+    #[test]
+    fn unclosed_placeholder() {
+        assert_eq!(
+            Template::parse("job {{job_id").unwrap_err(),
+            "unclosed placeholder at byte 4"
+        );
+    }
+
+    #[test]
+    fn unknown_placeholder() {
+        assert_eq!(
+            Template::parse("{{job}}").unwrap_err(),
+            "unknown placeholder {{job}}"
+        );
+    }
+
+    #[test]
+    fn unknown_filter() {
+        assert_eq!(
+            Template::parse("{{log | upper}}").unwrap_err(),
+            "unknown filter upper of {{log}}"
+        );
+    }
+}